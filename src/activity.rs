@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use crate::utils;
+
+// How restless the parrot is over time. The day runs through a cycle of phases (by
+// default a busy five minutes followed by two calm ones, over and over), each setting
+// how long the parrot sits between moves, how likely it is to get up once it has sat
+// long enough, and how fast it goes. Towards the end of a phase it eases into the next
+// one over ACTIVITY_BLEND_SECONDS, so the change isn't sudden.
+
+/// One stretch of the activity cycle.
+#[derive(Clone, Copy, Debug)]
+pub struct Phase {
+    pub seconds: u32,     // How long it lasts
+    pub idle: (u32, u32), // Frames the parrot sits still before it may move, shortest and longest
+    pub move_chance: f32, // Chance it moves once it has sat long enough, otherwise it sits again
+    pub speed: f32,       // Multiplier on the random speed (0.5-3.0)
+}
+
+// The old constant behaviour, used without any phases
+const STEADY: Phase = Phase { seconds: 0, idle: (180, 780), move_chance: 1.0, speed: 1.0 };
+
+impl Phase {
+    /// A random idle time in the phase's range, `roll` being between 0 and 1.
+    pub fn idle_frames(&self, roll: f32) -> u32 {
+        let (shortest, longest) = self.idle;
+        shortest + (roll * longest.saturating_sub(shortest) as f32) as u32
+    }
+
+    // Part way (`t` from 0 to 1) from this phase to `other`
+    fn towards(&self, other: &Phase, t: f32) -> Phase {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Phase {
+            seconds: self.seconds,
+            idle: (mix(self.idle.0 as f32, other.idle.0 as f32).round() as u32, mix(self.idle.1 as f32, other.idle.1 as f32).round() as u32),
+            move_chance: mix(self.move_chance, other.move_chance),
+            speed: mix(self.speed, other.speed),
+        }
+    }
+}
+
+pub struct Activity {
+    phases: &'static [Phase],
+    start: Instant,
+}
+
+impl Activity {
+    /// Starts the cycle at its first phase.
+    pub fn new(phases: &'static [Phase]) -> Self {
+        Activity { phases, start: Instant::now() }
+    }
+
+    /// How restless the parrot is right now.
+    pub fn energy(&self) -> Phase {
+        self.at(self.start.elapsed().as_secs_f32())
+    }
+
+    // The phase `seconds` into the cycle, blended into the next one near its end
+    fn at(&self, seconds: f32) -> Phase {
+        let cycle: u32 = self.phases.iter().map(|phase| phase.seconds).sum();
+        if cycle == 0 {
+            return self.phases.first().copied().unwrap_or(STEADY);
+        }
+        let mut time = seconds % cycle as f32;
+        for (index, phase) in self.phases.iter().enumerate() {
+            if time < phase.seconds as f32 {
+                let next = &self.phases[(index + 1) % self.phases.len()];
+                let blend = utils::ACTIVITY_BLEND_SECONDS.min(phase.seconds) as f32;
+                let left = phase.seconds as f32 - time;
+                return if left < blend { phase.towards(next, 1.0 - left / blend) } else { *phase };
+            }
+            time -= phase.seconds as f32;
+        }
+        // Only reached through rounding at the very end of the cycle
+        self.phases[0]
+    }
+}
@@ -0,0 +1,212 @@
+use crate::deck::Card;
+use crate::markup;
+use crate::utils;
+
+// Importing cards from Anki. Two kinds of file are understood:
+//   - the "Notes in Plain Text" export: tab separated, one note per line, optionally
+//     preceded by `#key:value` header lines (Anki 2.1.55 and later)
+//   - .apkg packages (a zip holding a SQLite collection), when built with the `apkg` feature
+// Note fields are mapped to card fields by position using `ANKI_FIELDS`.
+
+/// True if a .txt file looks like an Anki plain text export rather than a list of messages.
+pub fn is_text_export(content: &str) -> bool {
+    content.starts_with("#separator:") || content.starts_with("#html:")
+}
+
+/// Parses an Anki plain text export.
+pub fn parse_text_export(content: &str) -> Result<Vec<Card>, String> {
+    // Header lines say which character separates fields and which columns aren't note
+    // fields. Column numbers in the header start at 1.
+    let mut separator = b'\t';
+    let mut tags_column = None;
+    let mut skip_columns = Vec::new();
+    for line in content.lines().take_while(|line| line.starts_with('#')) {
+        let Some((key, value)) = line[1..].split_once(':') else { continue };
+        let column = value.trim().parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+        match key.trim() {
+            "separator" => {
+                separator = match value.trim() {
+                    "tab" | "Tab" => b'\t',
+                    "comma" | "Comma" => b',',
+                    "semicolon" | "Semicolon" => b';',
+                    "space" | "Space" => b' ',
+                    "pipe" | "Pipe" => b'|',
+                    "colon" | "Colon" => b':',
+                    other => other.bytes().next().unwrap_or(b'\t'),
+                };
+            }
+            "tags column" => tags_column = column,
+            "guid column" | "notetype column" | "deck column" => skip_columns.extend(column),
+            _ => {}
+        }
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(separator)
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes());
+    let mut cards = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let mut fields = Vec::new();
+        let mut tags = "";
+        for (column, field) in record.iter().enumerate() {
+            if Some(column) == tags_column {
+                tags = field;
+            } else if !skip_columns.contains(&column) {
+                fields.push(field);
+            }
+        }
+        if let Some(card) = note_to_card(&fields, tags) {
+            cards.push(card);
+        }
+    }
+    Ok(cards)
+}
+
+/// Loads the notes from an .apkg package.
+#[cfg(feature = "apkg")]
+pub fn load_package(path: &str) -> Result<Vec<Card>, String> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    // collection.anki21 is the newer schema, collection.anki2 is kept for old clients.
+    // Packages that only have the zstd compressed collection.anki21b aren't supported.
+    let name = ["collection.anki21", "collection.anki2"]
+        .into_iter()
+        .find(|name| archive.by_name(name).is_ok())
+        .ok_or_else(|| "no collection.anki21 or collection.anki2 in package (export with \"Support older Anki versions\" ticked)".to_string())?;
+    let mut collection = Vec::new();
+    archive.by_name(name).map_err(|e| e.to_string())?
+        .read_to_end(&mut collection).map_err(|e| e.to_string())?;
+
+    // SQLite needs a real file to open
+    let temp_path = std::env::temp_dir().join(format!("parrot-pet-{}.anki2", std::process::id()));
+    std::fs::write(&temp_path, &collection).map_err(|e| e.to_string())?;
+    let result = read_collection(&temp_path);
+    let _ = std::fs::remove_file(&temp_path);
+    result
+}
+
+#[cfg(feature = "apkg")]
+fn read_collection(path: &std::path::Path) -> Result<Vec<Card>, String> {
+    let connection = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())?;
+    let mut statement = connection.prepare("SELECT flds, tags FROM notes ORDER BY id").map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut cards = Vec::new();
+    for row in rows {
+        let (fields, tags) = row.map_err(|e| e.to_string())?;
+        // Fields are stored in one column separated by the unit separator character
+        let fields: Vec<&str> = fields.split('\u{1f}').collect();
+        if let Some(card) = note_to_card(&fields, &tags) {
+            cards.push(card);
+        }
+    }
+    Ok(cards)
+}
+
+/// Turns a note's fields into a card using `ANKI_FIELDS`. A note with just two fields
+/// (Anki's Basic note type) is read as front = term, back = meaning whatever the mapping.
+fn note_to_card(fields: &[&str], tags: &str) -> Option<Card> {
+    let fields: Vec<String> = fields.iter().map(|field| clean_field(field)).collect();
+    let mut card = Card::default();
+    if fields.len() == 2 {
+        card.term = fields[0].clone();
+        card.meaning = fields[1].clone();
+    } else {
+        for (name, value) in utils::ANKI_FIELDS.iter().zip(&fields) {
+            let value = Some(value.clone()).filter(|v| !v.is_empty());
+            match *name {
+                "term" => card.term = value.unwrap_or_default(),
+                "reading" => card.reading = value,
+                "meaning" => card.meaning = value.unwrap_or_default(),
+                "example" => card.example = value,
+                _ => {}
+            }
+        }
+    }
+    card.tags = tags.split_whitespace().map(|tag| tag.to_string()).collect();
+    if card.term.is_empty() { None } else { Some(card) }
+}
+
+/// Converts a field's HTML into bubble markup: line breaks and bold are kept, other tags
+/// and sound/image references are dropped and entities are decoded. What is left is
+/// text, escaped so none of it is read as markup.
+fn clean_field(field: &str) -> String {
+    let mut out = String::new();
+    let mut rest = field;
+    while let Some(ch) = rest.chars().next() {
+        // As in HTML, a `<` not followed by a tag name is just text
+        if ch == '<'
+            && rest[1..].starts_with(|next: char| next.is_ascii_alphabetic() || next == '/' || next == '!')
+            && let Some(end) = rest.find('>')
+        {
+            let tag = rest[1..end].trim().to_ascii_lowercase();
+            let name = tag.trim_start_matches('/').split([' ', '/']).next().unwrap_or("");
+            match name {
+                "br" => out.push_str("<br>"),
+                "b" | "strong" => out.push_str(if tag.starts_with('/') { "</b>" } else { "<b>" }),
+                // A new block starts on a new line
+                "div" | "p" if !tag.starts_with('/') && !out.is_empty() && !out.ends_with("<br>") => out.push_str("<br>"),
+                _ => {}
+            }
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if ch == '['
+            && let Some(end) = rest.find(']')
+            && rest[1..end].starts_with("sound:")
+        {
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if ch == '&'
+            && let Some(end) = rest.find(';').filter(|&end| end <= 10)
+            && let Some(decoded) = markup::decode_entity(&rest[1..end])
+        {
+            out.push_str(&markup::escape(&decoded.to_string()));
+            rest = &rest[end + 1..];
+            continue;
+        }
+        out.push_str(&markup::escape(&ch.to_string()));
+        rest = &rest[ch.len_utf8()..];
+    }
+    let out = out.trim();
+    out.strip_suffix("<br>").unwrap_or(out).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup::{self, Style};
+
+    // The text a field ends up drawing, and whether any of it is bold
+    fn drawn(field: &str) -> (String, bool) {
+        let chars = markup::parse(&clean_field(field), Style::default());
+        (chars.iter().map(|c| c.ch).collect(), chars.iter().any(|c| c.style.bold))
+    }
+
+    #[test]
+    fn fields_keep_breaks_and_bold() {
+        assert_eq!(clean_field("<div>猫</div><div>cat</div>"), "猫<br>cat");
+        assert_eq!(clean_field("<b>夢</b>&nbsp;dream [sound:yume.mp3]"), "<b>夢</b> dream");
+        assert_eq!(drawn("<strong>夢</strong>"), ("夢".to_string(), true));
+    }
+
+    #[test]
+    fn field_text_is_never_markup() {
+        // Decoded entities and stray brackets are drawn as they are
+        assert_eq!(drawn("a &lt;s=40&gt; b"), ("a <s=40> b".to_string(), false));
+        assert_eq!(drawn("x &lt;b&gt;y&lt;/b&gt;"), ("x <b>y</b>".to_string(), false));
+        assert_eq!(drawn("1 < 2 &amp;&amp; 3 > 2"), ("1 < 2 && 3 > 2".to_string(), false));
+        assert_eq!(drawn("AT&amp;T &amp;lt;"), ("AT&T &lt;".to_string(), false));
+        let big = markup::parse(&clean_field("a &lt;s=40&gt; b"), Style::default());
+        assert!(big.iter().all(|c| c.style.px == Style::default().px));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::markup::Style;
+use crate::text::{Line, PlacedGlyph};
+use crate::utils;
+
+// Speech bubble styles and animations. The animations are all driven by frame counters:
+// a scale "pop" and fade-in when the bubble opens, a fade-out before its last page
+// closes, and an optional typewriter reveal of each page's text.
+
+/// The kind of bubble a message is shown in, each with its own art and text style.
+/// A message can pick one with a leading tag such as `<bubble=thought>`, otherwise
+/// it gets the default for where it came from (see `MESSAGE_BUBBLE_STYLE` and
+/// `REMINDER_BUBBLE_STYLE` in utils).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BubbleStyle {
+    Speech,
+    Thought,
+    Shout,
+    Whisper,
+}
+
+impl BubbleStyle {
+    pub const ALL: [BubbleStyle; 4] = [BubbleStyle::Speech, BubbleStyle::Thought, BubbleStyle::Shout, BubbleStyle::Whisper];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "speech" | "say" => Some(BubbleStyle::Speech),
+            "thought" | "think" => Some(BubbleStyle::Thought),
+            "shout" => Some(BubbleStyle::Shout),
+            "whisper" => Some(BubbleStyle::Whisper),
+            _ => None,
+        }
+    }
+
+    /// Bubble art for this style. All of them share bubble.png's size, text area and tail position.
+    pub fn asset(&self) -> &'static str {
+        match self {
+            BubbleStyle::Speech => "assets/bubble.png",
+            BubbleStyle::Thought => "assets/bubble_thought.png",
+            BubbleStyle::Shout => "assets/bubble_shout.png",
+            BubbleStyle::Whisper => "assets/bubble_whisper.png",
+        }
+    }
+
+    /// Style the message text starts out in, markup inside the message still applies on top.
+    pub fn text_style(&self) -> Style {
+        match self {
+            BubbleStyle::Speech => Style::default(),
+            BubbleStyle::Thought => Style { color: [80, 80, 110], ..Default::default() },
+            BubbleStyle::Shout => Style { bold: true, color: [200, 0, 0], ..Default::default() },
+            BubbleStyle::Whisper => Style { px: utils::FONT_SIZE_MAIN - 2.0, color: [120, 120, 120], ..Default::default() },
+        }
+    }
+}
+
+/// Splits a leading `<bubble=...>` tag off a message. Returns the style it names, if any,
+/// and the rest of the message; unknown styles are left in the text.
+pub fn split_style(message: &str) -> (Option<BubbleStyle>, &str) {
+    let trimmed = message.trim_start();
+    if let Some(rest) = trimmed.strip_prefix("<bubble=")
+        && let Some(end) = rest.find('>')
+        && let Some(style) = BubbleStyle::from_name(&rest[..end])
+    {
+        return (Some(style), &rest[end + 1..]);
+    }
+    (None, message)
+}
+
+/// How the bubble should look on a given frame.
+#[derive(Clone, Copy, PartialEq)]
+pub struct BubbleFrame {
+    pub scale: f32,   // 1.0 = full size
+    pub opacity: f32, // 1.0 = fully opaque
+    pub timer: u32,   // Frames the current page has been shown, drives the typewriter
+}
+
+impl BubbleFrame {
+    /// Computes the frame for a bubble that opened `open_timer` frames ago and has shown
+    /// its current page for `page_timer` out of `page_duration` frames.
+    /// Only the last page fades out, earlier ones are followed by the next page.
+    pub fn at(open_timer: u32, page_timer: u32, page_duration: u32, last_page: bool) -> Self {
+        let pop = (open_timer as f32 / utils::BUBBLE_POP_FRAMES.max(1) as f32).min(1.0);
+        let fade_out = if last_page {
+            let remaining = page_duration.saturating_sub(page_timer);
+            (remaining as f32 / utils::BUBBLE_FADE_FRAMES.max(1) as f32).min(1.0)
+        } else {
+            1.0
+        };
+        BubbleFrame {
+            scale: ease_out_back(pop),
+            opacity: pop.min(fade_out),
+            timer: page_timer,
+        }
+    }
+
+    /// True if this frame is drawn any differently from a fully open bubble.
+    pub fn is_transitioning(&self) -> bool {
+        self.scale != 1.0 || self.opacity != 1.0
+    }
+}
+
+/// Overshoots slightly past 1.0 before settling, which reads as a "pop".
+fn ease_out_back(t: f32) -> f32 {
+    let overshoot = 1.70158;
+    let t = t - 1.0;
+    1.0 + (overshoot + 1.0) * t * t * t + overshoot * t * t
+}
+
+/// Time a character takes to type out, in units of one Latin letter.
+/// A CJK character carries a whole syllable or word, so it is given longer.
+fn reveal_weight(ch: char) -> f32 {
+    if ch.is_whitespace() {
+        0.0
+    } else if is_cjk(ch) {
+        utils::TYPEWRITER_CJK_WEIGHT
+    } else {
+        1.0
+    }
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch,
+        '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Halfwidth and fullwidth forms
+    )
+}
+
+/// Number of frames needed to type out the given characters.
+pub fn typing_frames(chars: impl Iterator<Item = char>) -> u32 {
+    if !utils::TYPEWRITER_ENABLED {
+        return 0;
+    }
+    let weight: f32 = chars.map(reveal_weight).sum();
+    (weight * 60.0 / utils::TYPEWRITER_CHARS_PER_SECOND).ceil() as u32
+}
+
+/// How long a page stays up: long enough to type it out and then read it.
+pub fn page_duration(page: &[Line]) -> u32 {
+    let chars = || page.iter().flat_map(|line| &line.chars).map(|c| c.ch);
+    let weight: f32 = chars().map(reveal_weight).sum();
+    let reading = (weight * utils::BUBBLE_READING_FRAMES_PER_CHAR as f32) as u32;
+    typing_frames(chars()) + reading.max(utils::BUBBLE_MIN_PAGE_FRAMES)
+}
+
+/// How long `page` of `pages` stays up and whether it is the last one. A question (or
+/// anything else `asking` for an answer) stays up at least QUIZ_ANSWER_FRAMES, and no
+/// pages at all have no time.
+pub fn page_timing(pages: &[Vec<Line>], page: usize, asking: bool) -> (u32, bool) {
+    let duration = pages.get(page).map_or(0, |lines| {
+        let duration = page_duration(lines);
+        if asking { duration.max(utils::QUIZ_ANSWER_FRAMES) } else { duration }
+    });
+    (duration, page + 1 >= pages.len())
+}
+
+/// How many of the laid out glyphs are visible `timer` frames after the bubble opened.
+pub fn visible_glyphs(glyphs: &[PlacedGlyph], timer: u32) -> usize {
+    if !utils::TYPEWRITER_ENABLED {
+        return glyphs.len();
+    }
+    let budget = timer as f32 * utils::TYPEWRITER_CHARS_PER_SECOND / 60.0;
+    let mut spent = 0.0;
+    for (i, glyph) in glyphs.iter().enumerate() {
+        spent += reveal_weight(glyph.ch);
+        if spent > budget {
+            return i;
+        }
+    }
+    glyphs.len()
+}
+
+/// Composites a BGRA layer onto `dest` scaled around `anchor` and multiplied by `opacity`.
+/// Both buffers are `width` x `height`, premultiplied.
+pub fn composite(dest: &mut [u8], layer: &[u8], width: usize, height: usize, anchor: (f32, f32), scale: f32, opacity: f32) {
+    if scale <= 0.0 || opacity <= 0.0 {
+        return;
+    }
+    let alpha_scale = (opacity.min(1.0) * 256.0) as u32;
+    for y in 0..height {
+        // Nearest neighbour: map each destination pixel back into the unscaled layer
+        let src_y = anchor.1 + (y as f32 - anchor.1) / scale;
+        if src_y < 0.0 || src_y >= height as f32 {
+            continue;
+        }
+        for x in 0..width {
+            let src_x = anchor.0 + (x as f32 - anchor.0) / scale;
+            if src_x < 0.0 || src_x >= width as f32 {
+                continue;
+            }
+            let src_idx = (src_y as usize * width + src_x as usize) * 4;
+            let alpha = layer[src_idx + 3] as u32 * alpha_scale / 256;
+            if alpha == 0 {
+                continue;
+            }
+            let dest_idx = (y * width + x) * 4;
+            for channel in 0..3 {
+                let src = layer[src_idx + channel] as u32 * alpha_scale / 256;
+                dest[dest_idx + channel] = (src + dest[dest_idx + channel] as u32 * (255 - alpha) / 255) as u8;
+            }
+            dest[dest_idx + 3] = (alpha + dest[dest_idx + 3] as u32 * (255 - alpha) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup::StyledChar;
+
+    fn pages(texts: &[&str]) -> Vec<Vec<Line>> {
+        texts.iter()
+            .map(|text| vec![Line::ltr(text.chars().map(|ch| StyledChar { ch, style: Style::default() }).collect())])
+            .collect()
+    }
+
+    // Frames a bubble opened on `pages` stays up, timed the way the main loop does it
+    fn frames_open(pages: &[Vec<Line>], asking: bool) -> u32 {
+        let (mut page, mut page_timer, mut frames) = (0, 0, 0);
+        loop {
+            let (duration, last_page) = page_timing(pages, page, asking);
+            frames += 1;
+            page_timer += 1;
+            if page_timer >= duration {
+                page_timer = 0;
+                if last_page {
+                    return frames;
+                }
+                page += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn a_new_bubble_stays_up_for_its_pages() {
+        let one = pages(&["Drink some water"]);
+        assert_eq!(frames_open(&one, false), page_duration(&one[0]));
+        let two = pages(&["First page", "Second page, a little longer"]);
+        assert_eq!(frames_open(&two, false), page_duration(&two[0]) + page_duration(&two[1]));
+    }
+
+    #[test]
+    fn questions_stay_up_long_enough_to_answer() {
+        let question = pages(&["犬"]);
+        assert_eq!(frames_open(&question, true), page_duration(&question[0]).max(utils::QUIZ_ANSWER_FRAMES));
+    }
+
+    #[test]
+    fn no_pages_have_no_time() {
+        assert_eq!(page_timing(&[], 0, true), (0, true));
+    }
+}
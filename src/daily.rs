@@ -0,0 +1,182 @@
+use std::fs;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::library::{CardId, Library};
+use crate::scheduler::Clock;
+use crate::utils;
+
+// Word of the day. Every day has one card, picked from all the decks with the date as
+// the seed, so it stays the same all day (and is the same for everyone with the same
+// decks). The first right-click of the day announces it instead of the next card, and
+// Ctrl+right-click brings it back whenever wanted. The day it was last announced is
+// saved, so restarting the parrot doesn't announce it again. Days follow the same clock
+// as the reminders (see scheduler.rs).
+
+pub struct WordOfTheDay {
+    path: String,
+    clock: Box<dyn Clock>,
+    announced: Option<NaiveDate>,
+}
+
+impl WordOfTheDay {
+    pub fn load(path: &str, clock: Box<dyn Clock>) -> Self {
+        let announced = fs::read_to_string(path).ok().and_then(|content| content.trim().parse().ok());
+        WordOfTheDay { path: path.to_string(), clock, announced }
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.clock.now().date()
+    }
+
+    /// True if today's word hasn't been announced yet.
+    pub fn due(&self) -> bool {
+        self.announced != Some(self.today())
+    }
+
+    /// Marks today's word announced, once it has been shown.
+    pub fn announced(&mut self) {
+        let today = self.today();
+        if self.announced == Some(today) {
+            return;
+        }
+        self.announced = Some(today);
+        if let Err(e) = fs::write(&self.path, today.to_string()) {
+            eprintln!("Failed to save {}: {}", self.path, e);
+        }
+    }
+
+    /// Today's card, `None` if there are no cards at all. Vocabulary cards (those with a
+    /// meaning) are preferred over free-form messages.
+    pub fn card(&self, library: &Library) -> Option<CardId> {
+        let mut rng = utils::SimpleRng::with_seed(seed(self.today()));
+        library.any(|card| !card.meaning.is_empty(), &mut rng)
+    }
+}
+
+// The random seed for `date`. Consecutive days are hashed (splitmix64) so their seeds,
+// and so their words, have nothing to do with each other.
+fn seed(date: NaiveDate) -> u64 {
+    let mut z = (date.num_days_from_ce() as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The bubble message announcing `message` as the word of the day.
+pub fn message(message: &str) -> String {
+    format!("<c=#888><s=12>Word of the day</s></c><br>{}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::deck::{Card, Deck};
+
+    // A clock the test moves on a day at a time
+    #[derive(Clone)]
+    struct FakeClock(Rc<Cell<NaiveDate>>);
+
+    impl FakeClock {
+        fn on(year: i32, month: u32, day: u32) -> Self {
+            FakeClock(Rc::new(Cell::new(NaiveDate::from_ymd_opt(year, month, day).unwrap())))
+        }
+
+        fn next_day(&self) {
+            self.0.set(self.0.get().succ_opt().unwrap());
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0.get().and_hms_opt(9, 0, 0).unwrap()
+        }
+    }
+
+    // A fresh state file for one test
+    fn state_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("parrot-daily-{}-{}.txt", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    // One deck with `words` vocabulary cards and `lines` free-form ones
+    fn library(words: usize, lines: usize) -> Library {
+        let mut cards: Vec<Card> = (0..lines).map(|line| Card::legacy(&format!("line {}", line))).collect();
+        for word in 0..words {
+            let mut card = Card::legacy(&format!("word {}", word));
+            card.fill(Some("よみ".to_string()), "meaning".to_string());
+            cards.push(card);
+        }
+        let deck = Deck { name: "test".to_string(), cards, tags: Vec::new(), picker: utils::DEFAULT_PICKER, tag_weights: HashMap::new() };
+        Library::new(vec![deck])
+    }
+
+    #[test]
+    fn the_same_day_has_the_same_card() {
+        let library = library(50, 0);
+        let clock = FakeClock::on(2026, 10, 19);
+        let first = WordOfTheDay::load(&state_file("same"), Box::new(clock.clone()));
+        let second = WordOfTheDay::load(&state_file("same"), Box::new(clock.clone()));
+        assert!(first.card(&library).is_some());
+        assert_eq!(first.card(&library), second.card(&library));
+        assert_eq!(first.card(&library), first.card(&library));
+    }
+
+    #[test]
+    fn days_spread_across_the_deck() {
+        let library = library(10, 0);
+        let clock = FakeClock::on(2026, 1, 1);
+        let word = WordOfTheDay::load(&state_file("spread"), Box::new(clock.clone()));
+        let mut shown = vec![0; 10];
+        let mut repeats = 0;
+        let mut last = None;
+        for _ in 0..200 {
+            let card = word.card(&library).unwrap().card;
+            shown[card] += 1;
+            repeats += usize::from(last == Some(card));
+            last = Some(card);
+            clock.next_day();
+        }
+        // About 20 days each, and about one day in ten the same as the day before
+        assert!(shown.iter().all(|&days| (8..=35).contains(&days)), "{:?}", shown);
+        assert!(repeats < 40, "{} repeats", repeats);
+    }
+
+    #[test]
+    fn words_are_preferred_to_free_form_lines() {
+        let clock = FakeClock::on(2026, 1, 1);
+        let word = WordOfTheDay::load(&state_file("words"), Box::new(clock.clone()));
+        let mixed = library(2, 20);
+        for _ in 0..30 {
+            let card = word.card(&mixed).unwrap();
+            assert!(!mixed.card(card).meaning.is_empty());
+            clock.next_day();
+        }
+        // With no words any card will do, with no cards there is nothing
+        assert!(word.card(&library(0, 3)).is_some());
+        assert_eq!(word.card(&library(0, 0)), None);
+    }
+
+    #[test]
+    fn it_is_announced_once_a_day() {
+        let path = state_file("announced");
+        let clock = FakeClock::on(2026, 10, 19);
+        let mut word = WordOfTheDay::load(&path, Box::new(clock.clone()));
+        assert!(word.due());
+        word.announced();
+        assert!(!word.due());
+        // Not again after a restart the same day
+        assert!(!WordOfTheDay::load(&path, Box::new(clock.clone())).due());
+        clock.next_day();
+        assert!(word.due());
+        assert!(WordOfTheDay::load(&path, Box::new(clock.clone())).due());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::anki;
+use crate::kana;
+use crate::picker::Strategy;
+use crate::utils;
+
+// Vocabulary decks. A deck is a list of cards with separate fields for the term, its
+// reading, meaning and so on, so every card is shown the same way. Decks can be written
+// in TOML, JSON or CSV, or imported from Anki (see anki.rs); a plain text file with one
+// free-form message per line (the old messages.txt) is still accepted and each line
+// becomes a card shown as-is.
+
+/// A single vocabulary card.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Card {
+    pub term: String,
+    #[serde(default)]
+    pub reading: Option<String>,
+    #[serde(default)]
+    pub meaning: String,
+    #[serde(default)]
+    pub example: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, deserialize_with = "jlpt_level")]
+    pub jlpt: Option<u8>, // 5 (easiest) to 1
+    // Lines from a plain text deck are shown exactly as written
+    #[serde(skip)]
+    legacy: bool,
+}
+
+impl Card {
+    /// Wraps a free-form line from a plain text deck.
+    pub fn legacy(line: &str) -> Self {
+        Card { term: line.to_string(), legacy: true, ..Default::default() }
+    }
+
+    /// True if the card is missing its reading or meaning. A line from a plain text deck
+    /// counts only if it is a single bare word.
+    pub fn is_bare(&self) -> bool {
+        if self.legacy {
+            !self.term.is_empty() && !self.term.contains(char::is_whitespace) && !self.term.contains('<')
+        } else {
+            self.reading.is_none() || self.meaning.is_empty()
+        }
+    }
+
+    /// Fills in the reading and meaning where the card doesn't have them already.
+    /// A reading that is just the term again (a kana word) is left out.
+    pub fn fill(&mut self, reading: Option<String>, meaning: String) {
+        self.legacy = false;
+        if self.reading.is_none() {
+            self.reading = reading.filter(|reading| *reading != self.term);
+        }
+        if self.meaning.is_empty() {
+            self.meaning = meaning;
+        }
+    }
+
+    /// Identifies the card in saved review state. Two cards with the same term and
+    /// reading are the same word, whatever deck or position they come from.
+    pub fn key(&self) -> String {
+        match self.reading.as_deref() {
+            Some(reading) if !reading.is_empty() => format!("{} [{}]", self.term, reading),
+            _ => self.term.clone(),
+        }
+    }
+
+    /// The bubble message for this card, using the markup from `markup.rs`:
+    /// the term as a headword followed by its reading and JLPT level, then the meaning,
+    /// then the example sentence in grey. Readings are written in `READING_SCRIPT`, with
+    /// an extra line in `READING_LINE` if set.
+    pub fn message(&self) -> String {
+        if self.legacy {
+            return kana::convert_brackets(&self.term, utils::READING_SCRIPT, utils::READING_LINE);
+        }
+        let reading = self.reading.as_deref().filter(|r| !r.is_empty());
+        let mut message = format!("<s>{}</s>", self.term);
+        if let Some(reading) = reading {
+            message.push_str(&format!(" [{}]", utils::READING_SCRIPT.render(reading)));
+        }
+        if let Some(level) = self.jlpt {
+            message.push_str(&format!(" <c=#888><s=12>N{}</s></c>", level));
+        }
+        // A kana word has no separate reading, the term itself is read out
+        let spoken = reading.or(Some(self.term.as_str()).filter(|term| kana::is_kana(term)));
+        if let Some(line) = utils::READING_LINE
+            && let Some(spoken) = spoken
+        {
+            message.push_str(&format!("<br><c=#888>{}</c>", line.render(spoken)));
+        }
+        if !self.meaning.is_empty() {
+            message.push_str(&format!("<br>{}", self.meaning));
+        }
+        if let Some(example) = self.example.as_deref().filter(|e| !e.is_empty()) {
+            message.push_str(&format!("<br><c=#666>{}</c>", example));
+        }
+        message
+    }
+}
+
+/// A loaded deck: its cards and how the next one is picked.
+pub struct Deck {
+    pub name: String, // File name without the extension
+    pub cards: Vec<Card>,
+    pub tags: Vec<String>, // Tags every card in the deck has
+    pub picker: Strategy,
+    pub tag_weights: HashMap<String, f32>, // For the weighted picker, tags not listed weigh 1.0
+}
+
+impl Deck {
+    fn new(path: &str, cards: Vec<Card>) -> Self {
+        let name = Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(path).to_string();
+        Deck { name, cards, tags: Vec::new(), picker: utils::DEFAULT_PICKER, tag_weights: HashMap::new() }
+    }
+
+    /// How likely the weighted picker is to choose a card: its heaviest tag's weight.
+    pub fn weight(&self, card: &Card) -> f32 {
+        card.tags.iter()
+            .filter_map(|tag| self.tag_weights.get(tag))
+            .copied()
+            .reduce(f32::max)
+            .unwrap_or(1.0)
+    }
+
+    /// True if `card` or the deck itself is tagged `tag` (ignoring case). A JLPT level
+    /// works as a tag too, "N4" matches cards with `jlpt = 4`.
+    pub fn has_tag(&self, card: &Card, tag: &str) -> bool {
+        let level = tag.strip_prefix(['N', 'n']).and_then(|digits| digits.parse::<u8>().ok());
+        card.tags.iter().chain(&self.tags).any(|own| own.eq_ignore_ascii_case(tag))
+            || (level.is_some() && card.jlpt == level)
+    }
+}
+
+// TOML and JSON decks keep their cards under a `cards` key, next to optional settings
+#[derive(Deserialize)]
+struct DeckFile {
+    cards: Vec<Card>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    picker: Option<String>, // See picker.rs for the names
+    #[serde(default)]
+    tag_weights: HashMap<String, f32>,
+}
+
+impl DeckFile {
+    fn into_deck(self, path: &str) -> Deck {
+        let mut deck = Deck::new(path, self.cards);
+        if let Some(name) = self.picker {
+            match Strategy::from_name(&name) {
+                Some(strategy) => deck.picker = strategy,
+                None => eprintln!("Unknown picker \"{}\" in {}, using the default", name, path),
+            }
+        }
+        deck.tags = self.tags;
+        deck.tag_weights = self.tag_weights;
+        deck
+    }
+}
+
+// CSV has no lists, so tags are one space separated column
+#[derive(Deserialize)]
+struct CsvCard {
+    term: String,
+    #[serde(default)]
+    reading: Option<String>,
+    #[serde(default)]
+    meaning: String,
+    #[serde(default)]
+    example: Option<String>,
+    #[serde(default)]
+    tags: String,
+    #[serde(default, deserialize_with = "jlpt_level")]
+    jlpt: Option<u8>,
+}
+
+/// Accepts a JLPT level written as a number (`3`) or as `"N3"`; empty means none.
+fn jlpt_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Level {
+        Number(u8),
+        Text(String),
+    }
+    match Option::<Level>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Level::Number(level)) => Ok(Some(level)),
+        Some(Level::Text(text)) => {
+            let text = text.trim();
+            if text.is_empty() {
+                return Ok(None);
+            }
+            let digits = text.strip_prefix(['N', 'n']).unwrap_or(text);
+            digits.parse().map(Some).map_err(|_| serde::de::Error::custom(format!("invalid JLPT level \"{}\"", text)))
+        }
+    }
+}
+
+/// Loads the first deck in `paths` that exists and parses, the format is picked by
+/// file extension (`.toml`, `.json`, `.csv`, `.tsv` and `.apkg` from Anki, anything else
+/// is plain text or an Anki text export).
+pub fn load(paths: &[&str]) -> Deck {
+    for path in paths {
+        if !Path::new(path).exists() {
+            continue;
+        }
+        match load_file(path) {
+            Ok(deck) => return deck,
+            Err(e) => eprintln!("Failed to load deck {}: {}", path, e),
+        }
+    }
+    eprintln!("No deck found, tried {}", paths.join(", "));
+    Deck::new("empty", Vec::new())
+}
+
+/// Loads every deck in the folder `dir`, in file name order. Files that fail to load are
+/// reported and left out.
+pub fn load_dir(dir: &str) -> Vec<Deck> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| path.to_str().map(|path| path.to_string()))
+        .collect();
+    paths.sort();
+    let mut decks = Vec::new();
+    for path in paths {
+        match load_file(&path) {
+            Ok(deck) => decks.push(deck),
+            Err(e) => eprintln!("Failed to load deck {}: {}", path, e),
+        }
+    }
+    decks
+}
+
+/// Loads a single deck file. Only TOML and JSON decks carry their own tags and picker settings.
+pub fn load_file(path: &str) -> Result<Deck, String> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    if extension == "apkg" {
+        #[cfg(feature = "apkg")]
+        return anki::load_package(path).map(|cards| Deck::new(path, cards));
+        #[cfg(not(feature = "apkg"))]
+        return Err("reading .apkg files needs the `apkg` feature (cargo build --features apkg)".to_string());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match extension.as_str() {
+        "toml" => toml::from_str::<DeckFile>(&content).map(|deck| deck.into_deck(path)).map_err(|e| e.to_string()),
+        "json" => parse_json(&content, path),
+        "csv" => parse_csv(&content).map(|cards| Deck::new(path, cards)),
+        "tsv" => anki::parse_text_export(&content).map(|cards| Deck::new(path, cards)),
+        _ if anki::is_text_export(&content) => anki::parse_text_export(&content).map(|cards| Deck::new(path, cards)),
+        _ => Ok(Deck::new(path, parse_text(&content))),
+    }
+}
+
+fn parse_json(content: &str, path: &str) -> Result<Deck, String> {
+    // Either `{"cards": [...]}` or just the array
+    let result = if content.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<Card>>(content).map(|cards| Deck::new(path, cards))
+    } else {
+        serde_json::from_str::<DeckFile>(content).map(|deck| deck.into_deck(path))
+    };
+    result.map_err(|e| e.to_string())
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Card>, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(content.as_bytes());
+    let mut cards = Vec::new();
+    for record in reader.deserialize::<CsvCard>() {
+        let record = record.map_err(|e| e.to_string())?;
+        cards.push(Card {
+            term: record.term,
+            reading: record.reading,
+            meaning: record.meaning,
+            example: record.example,
+            tags: record.tags.split_whitespace().map(|tag| tag.to_string()).collect(),
+            jlpt: record.jlpt,
+            legacy: false,
+        });
+    }
+    Ok(cards)
+}
+
+fn parse_text(content: &str) -> Vec<Card> {
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Card::legacy)
+        .collect()
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::deck::Card;
+use crate::markup;
+use crate::utils;
+
+// Offline dictionary lookups, so a deck can list just the words (`都市`, `夢`) and get
+// their readings and English meanings filled in from a local copy of JMdict (words) or
+// KANJIDIC2 (single kanji). Both are large XML files with one element per line, so they
+// are scanned line by line rather than parsed as a whole, and only when some card is
+// actually missing something.
+
+struct Entry {
+    reading: Option<String>,
+    meaning: String,
+    common: bool, // Marked as a common word, preferred when a spelling has several entries
+}
+
+/// Fills in missing readings and meanings from the first dictionary in `paths` that has them.
+pub fn enrich<'a>(cards: impl IntoIterator<Item = &'a mut Card>, paths: &[&str]) {
+    let mut cards: Vec<&mut Card> = cards.into_iter().collect();
+    let wanted: HashSet<String> = cards.iter().filter(|card| card.is_bare()).map(|card| card.term.clone()).collect();
+    if wanted.is_empty() {
+        return;
+    }
+    let mut found: HashMap<String, Entry> = HashMap::new();
+    for path in paths {
+        // Words found in an earlier dictionary aren't looked up again
+        let remaining: HashSet<&str> = wanted.iter()
+            .filter(|term| !found.contains_key(*term))
+            .map(|term| term.as_str())
+            .collect();
+        if remaining.is_empty() {
+            break;
+        }
+        let Ok(file) = File::open(path) else { continue };
+        if let Err(e) = scan(BufReader::new(file), &remaining, &mut found) {
+            eprintln!("Failed to read dictionary {}: {}", path, e);
+        }
+    }
+    for card in cards.iter_mut().filter(|card| card.is_bare()) {
+        if let Some(entry) = found.get(&card.term) {
+            card.fill(entry.reading.clone(), entry.meaning.clone());
+        }
+    }
+}
+
+// Collects each JMdict <entry> or KANJIDIC2 <character> and looks it up
+fn scan(reader: impl BufRead, wanted: &HashSet<&str>, found: &mut HashMap<String, Entry>) -> std::io::Result<()> {
+    let mut record = String::new();
+    let mut in_record = false;
+    for line in reader.lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed == "<entry>" || trimmed == "<character>" {
+            in_record = true;
+            record.clear();
+            continue;
+        }
+        if !in_record {
+            continue;
+        }
+        if trimmed == "</entry>" {
+            in_record = false;
+            read_word(&record, wanted, found);
+        } else if trimmed == "</character>" {
+            in_record = false;
+            read_kanji(&record, wanted, found);
+        } else {
+            record.push_str(trimmed);
+            record.push('\n');
+        }
+    }
+    Ok(())
+}
+
+// A JMdict entry: spellings in <keb>, readings in <reb>, meanings in <sense>/<gloss>
+fn read_word(record: &str, wanted: &HashSet<&str>, found: &mut HashMap<String, Entry>) {
+    let spellings = elements(record, "keb");
+    let readings = elements(record, "reb");
+    let matches: Vec<&String> = spellings.iter().chain(&readings)
+        .map(|(_, text)| text)
+        .filter(|text| wanted.contains(text.as_str()))
+        .collect();
+    if matches.is_empty() {
+        return;
+    }
+    // The first sense is the main meaning, glosses in other languages are skipped
+    let first_sense = elements(record, "sense").into_iter().next().map(|(_, sense)| sense).unwrap_or_default();
+    let glosses: Vec<String> = elements(&first_sense, "gloss")
+        .into_iter()
+        .filter(|(attributes, _)| !attributes.contains("xml:lang") || attributes.contains("\"eng\""))
+        .map(|(_, gloss)| gloss)
+        .take(utils::DICTIONARY_GLOSSES)
+        .collect();
+    let common = record.contains("<ke_pri>") || record.contains("<re_pri>");
+    for term in matches {
+        if found.get(term).is_some_and(|entry| entry.common || !common) {
+            continue;
+        }
+        let reading = readings.first().map(|(_, reading)| reading.clone());
+        found.insert(term.clone(), Entry { reading, meaning: glosses.join("; "), common });
+    }
+}
+
+// A KANJIDIC2 character: on and kun readings, English meanings have no m_lang attribute
+fn read_kanji(record: &str, wanted: &HashSet<&str>, found: &mut HashMap<String, Entry>) {
+    let Some((_, literal)) = elements(record, "literal").into_iter().next() else { return };
+    if !wanted.contains(literal.as_str()) || found.contains_key(&literal) {
+        return;
+    }
+    let readings: Vec<String> = elements(record, "reading")
+        .into_iter()
+        .filter(|(attributes, _)| attributes.contains("ja_on") || attributes.contains("ja_kun"))
+        .map(|(_, reading)| reading)
+        .take(utils::DICTIONARY_GLOSSES)
+        .collect();
+    let meanings: Vec<String> = elements(record, "meaning")
+        .into_iter()
+        .filter(|(attributes, _)| !attributes.contains("m_lang"))
+        .map(|(_, meaning)| meaning)
+        .take(utils::DICTIONARY_GLOSSES)
+        .collect();
+    let reading = if readings.is_empty() { None } else { Some(readings.join("、")) };
+    found.insert(literal, Entry { reading, meaning: meanings.join("; "), common: true });
+}
+
+/// Every `<name ...>text</name>` in `record`, as (attributes, decoded text).
+fn elements(record: &str, name: &str) -> Vec<(String, String)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut out = Vec::new();
+    let mut rest = record;
+    while let Some(start) = rest.find(&open) {
+        let after_name = &rest[start + open.len()..];
+        // Make sure this is <name> or <name attr>, not a longer tag like <keb_pri>
+        if !after_name.starts_with(['>', ' ']) {
+            rest = after_name;
+            continue;
+        }
+        let Some(tag_end) = after_name.find('>') else { break };
+        let attributes = after_name[..tag_end].trim().to_string();
+        let content = &after_name[tag_end + 1..];
+        let Some(end) = content.find(&close) else { break };
+        out.push((attributes, decode_entities(content[..end].trim())));
+        rest = &content[end + close.len()..];
+    }
+    out
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '&'
+            && let Some(end) = rest.find(';').filter(|&end| end <= 10)
+            && let Some(decoded) = markup::decode_entity(&rest[1..end])
+        {
+            out.push(decoded);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
@@ -0,0 +1,197 @@
+use fontdue::{Font, FontSettings};
+use ttf_parser::{colr, Face, GlyphId, RasterImageFormat, RgbaColor};
+
+// Colour emoji support for the bubble text.
+// fontdue only produces monochrome coverage, so emoji are drawn from a separate
+// colour font: embedded PNG bitmaps (CBDT/CBLC as in Noto Color Emoji, sbix as in
+// Apple Color Emoji) or layered COLR/CPAL glyphs (as in Segoe UI Emoji).
+
+/// Size and placement of a colour glyph, using the same conventions as fontdue's `Metrics`.
+#[derive(Clone, Copy, Default)]
+pub struct EmojiMetrics {
+    pub xmin: i32,
+    pub ymin: i32, // Distance from the baseline to the bottom of the bitmap (positive up)
+    pub width: usize,
+    pub height: usize,
+    pub advance_width: f32,
+}
+
+pub struct EmojiFont {
+    // Parsed once, the font stays loaded for the whole run so its bytes are leaked to
+    // let the face borrow them
+    face: Face<'static>,
+    // COLR layers are ordinary outlines in the same file, rasterized with fontdue
+    outlines: Option<Font>,
+}
+
+// Collects the COLR layers of a glyph: each outline followed by the colour it is painted with.
+// `None` means the layer uses the text foreground colour.
+struct LayerCollector {
+    current: Option<GlyphId>,
+    layers: Vec<(GlyphId, Option<RgbaColor>)>,
+}
+
+impl colr::Painter for LayerCollector {
+    fn outline(&mut self, glyph_id: GlyphId) {
+        self.current = Some(glyph_id);
+    }
+
+    fn paint_foreground(&mut self) {
+        if let Some(glyph_id) = self.current.take() {
+            self.layers.push((glyph_id, None));
+        }
+    }
+
+    fn paint_color(&mut self, color: RgbaColor) {
+        if let Some(glyph_id) = self.current.take() {
+            self.layers.push((glyph_id, Some(color)));
+        }
+    }
+}
+
+impl EmojiFont {
+    /// Loads a colour emoji font, returning `None` if it can't be read or parsed.
+    pub fn load(path: &str) -> Option<Self> {
+        let data: &'static [u8] = Box::leak(std::fs::read(path).ok()?.into_boxed_slice());
+        let face = Face::parse(data, 0).ok()?;
+        let has_colr = face.tables().colr.is_some();
+        let outlines = if has_colr {
+            Font::from_bytes(data, FontSettings::default()).ok()
+        } else {
+            None
+        };
+        Some(EmojiFont { face, outlines })
+    }
+
+    /// Returns true if the font has a colour glyph for this character.
+    pub fn has_glyph(&self, ch: char) -> bool {
+        let face = &self.face;
+        match face.glyph_index(ch) {
+            Some(glyph_id) => {
+                face.is_color_glyph(glyph_id) || face.glyph_raster_image(glyph_id, u16::MAX).is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the placement of a glyph without decoding or rasterizing it.
+    pub fn metrics(&self, ch: char, px: f32) -> Option<EmojiMetrics> {
+        let face = &self.face;
+        let glyph_id = face.glyph_index(ch)?;
+        let advance_width = face.glyph_hor_advance(glyph_id).unwrap_or(face.units_per_em()) as f32
+            * px / face.units_per_em() as f32;
+        if let Some(image) = face.glyph_raster_image(glyph_id, px.round() as u16) {
+            let scale = px / image.pixels_per_em as f32;
+            return Some(EmojiMetrics {
+                xmin: (image.x as f32 * scale).round() as i32,
+                ymin: (image.y as f32 * scale).round() as i32,
+                width: (image.width as f32 * scale).round() as usize,
+                height: (image.height as f32 * scale).round() as usize,
+                advance_width,
+            });
+        }
+        let layers = self.layers(face, glyph_id)?;
+        let outlines = self.outlines.as_ref()?;
+        let (left, bottom, right, top) = layer_bounds(outlines, &layers, px)?;
+        Some(EmojiMetrics {
+            xmin: left,
+            ymin: bottom,
+            width: (right - left) as usize,
+            height: (top - bottom) as usize,
+            advance_width,
+        })
+    }
+
+    /// Rasterizes a glyph into premultiplied BGRA pixels, `foreground` (RGB) is used
+    /// for COLR layers that take the text colour.
+    pub fn rasterize(&self, ch: char, px: f32, foreground: [u8; 3]) -> Option<(EmojiMetrics, Vec<u8>)> {
+        let face = &self.face;
+        let glyph_id = face.glyph_index(ch)?;
+        let metrics = self.metrics(ch, px)?;
+        if metrics.width == 0 || metrics.height == 0 {
+            return Some((metrics, Vec::new()));
+        }
+        if let Some(image) = face.glyph_raster_image(glyph_id, px.round() as u16) {
+            if image.format != RasterImageFormat::PNG {
+                return None;
+            }
+            let decoded = image::load_from_memory(image.data).ok()?.to_rgba8();
+            let scaled = image::imageops::resize(
+                &decoded,
+                metrics.width as u32,
+                metrics.height as u32,
+                image::imageops::FilterType::Triangle,
+            );
+            let mut pixels = Vec::with_capacity(metrics.width * metrics.height * 4);
+            for pixel in scaled.pixels() {
+                let [r, g, b, a] = pixel.0;
+                pixels.extend_from_slice(&[premultiply(b, a), premultiply(g, a), premultiply(r, a), a]);
+            }
+            return Some((metrics, pixels));
+        }
+
+        // COLR: paint each layer's coverage in its palette colour, bottom layer first
+        let layers = self.layers(face, glyph_id)?;
+        let outlines = self.outlines.as_ref()?;
+        let mut pixels = vec![0u8; metrics.width * metrics.height * 4];
+        for (layer_id, color) in layers {
+            let (layer_metrics, coverage) = outlines.rasterize_indexed(layer_id.0, px);
+            let (r, g, b, a) = match color {
+                Some(c) => (c.red, c.green, c.blue, c.alpha),
+                None => (foreground[0], foreground[1], foreground[2], 255),
+            };
+            // Offset of this layer inside the combined bitmap (which is y-down)
+            let offset_x = layer_metrics.xmin - metrics.xmin;
+            let offset_y = (metrics.ymin + metrics.height as i32) - (layer_metrics.ymin + layer_metrics.height as i32);
+            for y in 0..layer_metrics.height {
+                for x in 0..layer_metrics.width {
+                    let dest_x = x as i32 + offset_x;
+                    let dest_y = y as i32 + offset_y;
+                    if dest_x < 0 || dest_y < 0 || dest_x >= metrics.width as i32 || dest_y >= metrics.height as i32 {
+                        continue;
+                    }
+                    let alpha = coverage[y * layer_metrics.width + x] as u32 * a as u32 / 255;
+                    if alpha == 0 {
+                        continue;
+                    }
+                    let idx = (dest_y as usize * metrics.width + dest_x as usize) * 4;
+                    let src = [b as u32 * alpha / 255, g as u32 * alpha / 255, r as u32 * alpha / 255, alpha];
+                    for channel in 0..4 {
+                        pixels[idx + channel] = (src[channel] + pixels[idx + channel] as u32 * (255 - alpha) / 255) as u8;
+                    }
+                }
+            }
+        }
+        Some((metrics, pixels))
+    }
+
+    fn layers(&self, face: &Face<'_>, glyph_id: GlyphId) -> Option<Vec<(GlyphId, Option<RgbaColor>)>> {
+        if !face.is_color_glyph(glyph_id) {
+            return None;
+        }
+        let mut collector = LayerCollector { current: None, layers: Vec::new() };
+        face.paint_color_glyph(glyph_id, 0, &mut collector)?;
+        Some(collector.layers)
+    }
+}
+
+/// Union of all layer bounds as (left, bottom, right, top), y pointing up from the baseline.
+fn layer_bounds(outlines: &Font, layers: &[(GlyphId, Option<RgbaColor>)], px: f32) -> Option<(i32, i32, i32, i32)> {
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for (layer_id, _) in layers {
+        let m = outlines.metrics_indexed(layer_id.0, px);
+        if m.width == 0 || m.height == 0 {
+            continue;
+        }
+        let layer = (m.xmin, m.ymin, m.xmin + m.width as i32, m.ymin + m.height as i32);
+        bounds = Some(match bounds {
+            Some((l, b, r, t)) => (l.min(layer.0), b.min(layer.1), r.max(layer.2), t.max(layer.3)),
+            None => layer,
+        });
+    }
+    bounds
+}
+
+fn premultiply(channel: u8, alpha: u8) -> u8 {
+    (channel as u32 * alpha as u32 / 255) as u8
+}
@@ -0,0 +1,73 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike, Weekday};
+
+// Times of day something applies, like "09:00-18:00" on weekdays. A window that ends
+// before it starts runs past midnight ("22:00-06:00"), one that ends when it starts
+// lasts all day, and one without days applies every day. For a window past midnight,
+// the days are the ones it starts on.
+
+#[derive(Clone, Debug)]
+pub struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    days: Vec<Weekday>, // Empty means every day
+}
+
+impl Window {
+    /// Parses `hours` written as "HH:MM-HH:MM" and `days` as in `parse_days`.
+    pub fn parse(hours: &str, days: &[String]) -> Result<Self, String> {
+        let (start, end) = hours.split_once('-').ok_or_else(|| format!("hours \"{}\" should look like 09:00-18:00", hours))?;
+        Ok(Window { start: time(start)?, end: time(end)?, days: parse_days(days)? })
+    }
+
+    /// A window covering the whole of the given `days`.
+    pub fn all_day(days: &[String]) -> Result<Self, String> {
+        Window::parse("00:00-00:00", days)
+    }
+
+    /// True if the local time `now` is inside the window.
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start == self.end {
+            on(today)
+        } else if self.start < self.end {
+            on(today) && time >= self.start && time < self.end
+        } else {
+            // Past midnight: the evening part belongs to today, the morning part to yesterday
+            (time >= self.start && on(today)) || (time < self.end && on(today.pred()))
+        }
+    }
+
+    /// The first minute from `now` on that is outside the window, `None` if it never closes.
+    pub fn end_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = now.with_second(0)?.with_nanosecond(0)?;
+        // A week covers every day it can be open on
+        for _ in 0..8 * 24 * 60 {
+            if !self.contains(time) {
+                return Some(time);
+            }
+            time += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// Parses weekday names ("mon", "Tuesday"), "weekdays", "weekends" or "daily".
+pub fn parse_days(days: &[String]) -> Result<Vec<Weekday>, String> {
+    let mut parsed = Vec::new();
+    for day in days {
+        match day.trim().to_ascii_lowercase().as_str() {
+            "weekdays" => parsed.extend([Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]),
+            "weekends" => parsed.extend([Weekday::Sat, Weekday::Sun]),
+            "daily" => {}
+            name => parsed.push(name.parse().map_err(|_| format!("unknown day \"{}\"", day))?),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses "9:30" or "09:30".
+pub fn time(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").map_err(|_| format!("invalid time \"{}\"", text.trim()))
+}
@@ -0,0 +1,289 @@
+// Transliteration of Japanese readings between hiragana, katakana and Hepburn romaji,
+// for people who can't read kana yet. Anything that isn't kana (kanji, punctuation,
+// Latin letters) is passed through untouched. Romaji follows modern Hepburn without
+// macrons: a long vowel mark repeats the vowel (ラーメン → raamen), ん before a vowel
+// or y is written n' (きんようび → kin'youbi) and a small っ doubles the next consonant
+// (まっちゃ → matcha).
+
+/// How a reading is written in the bubble.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)] // Chosen in utils.rs
+pub enum Script {
+    AsWritten, // Leave the reading as it is in the deck
+    Hiragana,
+    Katakana,
+    Romaji,
+}
+
+impl Script {
+    pub fn render(&self, text: &str) -> String {
+        match self {
+            Script::AsWritten => text.to_string(),
+            Script::Hiragana => to_hiragana(text),
+            Script::Katakana => to_katakana(text),
+            Script::Romaji => to_romaji(text),
+        }
+    }
+}
+
+pub fn to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+pub fn to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// True if `text` is made up only of kana (and long vowel marks).
+pub fn is_kana(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| matches!(c, 'ぁ'..='ゖ' | 'ァ'..='ヺ' | 'ー' | '・'))
+}
+
+pub fn to_romaji(text: &str) -> String {
+    let chars: Vec<char> = to_hiragana(text).chars().collect();
+    let mut romaji = String::new();
+    let mut double = false; // After a small っ, the next consonant is doubled
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        i += 1;
+        match c {
+            'っ' => {
+                double = true;
+                continue;
+            }
+            'ー' => {
+                if let Some(vowel) = romaji.chars().last().filter(|c| "aeiou".contains(*c)) {
+                    romaji.push(vowel);
+                }
+                continue;
+            }
+            'ん' => {
+                romaji.push('n');
+                // Keep "kin'en" apart from "kinen"
+                if next.and_then(syllable).is_some_and(|next| next.starts_with(['a', 'i', 'u', 'e', 'o', 'y'])) {
+                    romaji.push('\'');
+                }
+                double = false;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(base) = syllable(c) else {
+            romaji.push(c);
+            double = false;
+            continue;
+        };
+        let mut sound = base.to_string();
+        match next {
+            // き + ゃ → kya, し + ゃ → sha
+            Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if sound.len() > 1 && sound.ends_with('i') => {
+                sound.pop();
+                if !matches!(sound.as_str(), "sh" | "ch" | "j") {
+                    sound.push('y');
+                }
+                sound.push_str(&syllable(small).unwrap_or_default()[1..]);
+                i += 1;
+            }
+            // Katakana extensions: ファ → fa, ティ → ti, ウィ → wi, ヴァ → va
+            Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) if sound.len() > 1 || sound == "u" => {
+                sound.pop();
+                if sound.is_empty() {
+                    sound.push('w');
+                }
+                sound.push_str(syllable(small).unwrap_or_default());
+                i += 1;
+            }
+            _ => {}
+        }
+        if double {
+            match sound.chars().next() {
+                Some('c') => romaji.push('t'),
+                Some(first) if !"aeiou".contains(first) => romaji.push(first),
+                _ => {}
+            }
+            double = false;
+        }
+        romaji.push_str(&sound);
+    }
+    romaji
+}
+
+/// Rewrites every bracketed kana reading in a free-form line ("<s>都市</s> [とし] town")
+/// in `script`, and with `line` set, also puts the reading in that script on a line of
+/// its own right after it.
+pub fn convert_brackets(text: &str, script: Script, line: Option<Script>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|close| open + close) else { break };
+        let reading = &rest[open + 1..close];
+        result.push_str(&rest[..open]);
+        if is_kana(reading) {
+            result.push_str(&format!("[{}]", script.render(reading)));
+            rest = &rest[close + 1..];
+            if let Some(line) = line {
+                result.push_str(&format!("<br><c=#888>{}</c>", line.render(reading)));
+                rest = rest.trim_start();
+                if !rest.is_empty() {
+                    result.push_str("<br>");
+                }
+            }
+        } else {
+            result.push_str(&rest[open..=close]);
+            rest = &rest[close + 1..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// Romaji for a single hiragana
+fn syllable(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a", 'い' | 'ぃ' => "i", 'う' | 'ぅ' => "u", 'え' | 'ぇ' => "e", 'お' | 'ぉ' => "o",
+        'か' | 'ゕ' => "ka", 'き' => "ki", 'く' => "ku", 'け' | 'ゖ' => "ke", 'こ' => "ko",
+        'が' => "ga", 'ぎ' => "gi", 'ぐ' => "gu", 'げ' => "ge", 'ご' => "go",
+        'さ' => "sa", 'し' => "shi", 'す' => "su", 'せ' => "se", 'そ' => "so",
+        'ざ' => "za", 'じ' => "ji", 'ず' => "zu", 'ぜ' => "ze", 'ぞ' => "zo",
+        'た' => "ta", 'ち' => "chi", 'つ' => "tsu", 'て' => "te", 'と' => "to",
+        'だ' => "da", 'ぢ' => "ji", 'づ' => "zu", 'で' => "de", 'ど' => "do",
+        'な' => "na", 'に' => "ni", 'ぬ' => "nu", 'ね' => "ne", 'の' => "no",
+        'は' => "ha", 'ひ' => "hi", 'ふ' => "fu", 'へ' => "he", 'ほ' => "ho",
+        'ば' => "ba", 'び' => "bi", 'ぶ' => "bu", 'べ' => "be", 'ぼ' => "bo",
+        'ぱ' => "pa", 'ぴ' => "pi", 'ぷ' => "pu", 'ぺ' => "pe", 'ぽ' => "po",
+        'ま' => "ma", 'み' => "mi", 'む' => "mu", 'め' => "me", 'も' => "mo",
+        'や' | 'ゃ' => "ya", 'ゆ' | 'ゅ' => "yu", 'よ' | 'ょ' => "yo",
+        'ら' => "ra", 'り' => "ri", 'る' => "ru", 'れ' => "re", 'ろ' => "ro",
+        'わ' | 'ゎ' => "wa", 'ゐ' => "i", 'ゑ' => "e", 'を' => "o",
+        'ゔ' => "vu",
+        '・' => " ",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `convert` over each (input, expected) pair, naming the input that went wrong
+    fn check(convert: impl Fn(&str) -> String, cases: &[(&str, &str)]) {
+        for (input, expected) in cases {
+            assert_eq!(convert(input), *expected, "converting {:?}", input);
+        }
+    }
+
+    #[test]
+    fn romaji_doubles_the_consonant_after_sokuon() {
+        check(to_romaji, &[
+            ("きって", "kitte"),
+            ("がっこう", "gakkou"),
+            ("まっちゃ", "matcha"),
+            ("いっしょ", "issho"),
+            ("ざっし", "zasshi"),
+            ("ベッド", "beddo"),
+            // Nothing to double at the end of a word
+            ("あっ", "a"),
+        ]);
+    }
+
+    #[test]
+    fn romaji_repeats_the_vowel_for_a_long_vowel_mark() {
+        check(to_romaji, &[
+            ("ラーメン", "raamen"),
+            ("コーヒー", "koohii"),
+            ("スーパー", "suupaa"),
+            ("ケーキ", "keeki"),
+            ("ー", ""),
+        ]);
+    }
+
+    #[test]
+    fn romaji_joins_yoon_into_one_syllable() {
+        check(to_romaji, &[
+            ("きゃく", "kyaku"),
+            ("りょこう", "ryokou"),
+            ("びょういん", "byouin"),
+            ("しゃしん", "shashin"),
+            ("ちゅうい", "chuui"),
+            ("じょうず", "jouzu"),
+            ("ぎゅうにゅう", "gyuunyuu"),
+        ]);
+    }
+
+    #[test]
+    fn romaji_reads_katakana_like_hiragana() {
+        check(to_romaji, &[
+            ("カタカナ", "katakana"),
+            ("キャンプ", "kyanpu"),
+            ("チョコレート", "chokoreeto"),
+            ("ファン", "fan"),
+            ("パーティー", "paatii"),
+            ("ウィキ", "wiki"),
+            ("ヴァイオリン", "vaiorin"),
+            ("コンピューター", "konpyuutaa"),
+            ("ソフト・クリーム", "sofuto kuriimu"),
+        ]);
+    }
+
+    #[test]
+    fn romaji_keeps_n_apart_from_what_follows() {
+        check(to_romaji, &[
+            ("きんようび", "kin'youbi"),
+            ("きんえん", "kin'en"),
+            ("きねん", "kinen"),
+            ("しんぶん", "shinbun"),
+        ]);
+    }
+
+    #[test]
+    fn romaji_passes_through_what_is_not_kana() {
+        check(to_romaji, &[
+            ("猫", "猫"),
+            ("日本ご", "日本go"),
+            ("abc", "abc"),
+            ("", ""),
+        ]);
+    }
+
+    #[test]
+    fn scripts_swap_only_the_kana() {
+        check(to_hiragana, &[("カタカナ", "かたかな"), ("ラーメン", "らーめん"), ("猫ネコ", "猫ねこ")]);
+        check(to_katakana, &[("ひらがな", "ヒラガナ"), ("きゃっ", "キャッ"), ("日本ご", "日本ゴ")]);
+    }
+
+    #[test]
+    fn brackets_are_rewritten_in_the_chosen_script() {
+        let romaji = |text: &str| convert_brackets(text, Script::Romaji, None);
+        check(romaji, &[
+            ("<s>都市</s> [とし] town", "<s>都市</s> [toshi] town"),
+            ("<s>喫茶店</s> [きっさてん] café", "<s>喫茶店</s> [kissaten] café"),
+            ("<s>牛乳</s> [ぎゅうにゅう]", "<s>牛乳</s> [gyuunyuu]"),
+            ("<s>珈琲</s> [コーヒー]", "<s>珈琲</s> [koohii]"),
+            // Brackets without a kana reading stay as they are
+            ("[sic] [猫] [", "[sic] [猫] ["),
+        ]);
+        let hiragana = |text: &str| convert_brackets(text, Script::Hiragana, None);
+        check(hiragana, &[("<s>珈琲</s> [コーヒー] coffee", "<s>珈琲</s> [こーひー] coffee")]);
+    }
+
+    #[test]
+    fn brackets_can_add_a_reading_line() {
+        let with_line = |text: &str| convert_brackets(text, Script::AsWritten, Some(Script::Romaji));
+        check(with_line, &[
+            ("<s>切手</s> [きって] stamp", "<s>切手</s> [きって]<br><c=#888>kitte</c><br>stamp"),
+            ("<s>拉麺</s> [ラーメン]", "<s>拉麺</s> [ラーメン]<br><c=#888>raamen</c>"),
+        ]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+
+use chrono::{Local, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::deck::{self, Card, Deck};
+use crate::hours::Window;
+use crate::picker::Picker;
+use crate::review::Reviews;
+use crate::utils;
+
+// All the decks the parrot knows (vocab, motivation, team jokes...) and which of them it
+// draws cards from. Every deck file in DECK_DIR is loaded, or without that folder the
+// single deck from DECK_FILES as before.
+//
+// ACTIVE_DECKS_FILE narrows things down with a list of filters, the first one that
+// applies at the moment wins:
+//
+//   [[filter]]
+//   decks = ["vocab"]          # Deck file names without the extension, none means all
+//   tags = ["N4", "verb"]      # Cards with any of these tags (or JLPT levels), none means all
+//   hours = "09:00-18:00"      # Only at these times (see hours.rs), none means all day
+//   days = ["weekdays"]        # Only on these days, none means every day
+//
+// Without the file, or when no filter applies, every deck is active. Middle-clicking
+// the parrot pins one deck at a time, overriding the filters until it cycles back.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CardId {
+    pub deck: usize,
+    pub card: usize,
+}
+
+#[derive(Deserialize, Default)]
+struct FilterFile {
+    #[serde(default)]
+    filter: Vec<FilterEntry>,
+}
+
+#[derive(Deserialize)]
+struct FilterEntry {
+    #[serde(default)]
+    decks: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    hours: Option<String>,
+    #[serde(default)]
+    days: Vec<String>,
+}
+
+struct Filter {
+    decks: Vec<String>,
+    tags: Vec<String>,
+    window: Option<Window>,
+}
+
+impl Filter {
+    fn applies(&self, now: NaiveDateTime) -> bool {
+        self.window.as_ref().is_none_or(|window| window.contains(now))
+    }
+
+    fn includes(&self, deck: &Deck, card: &Card) -> bool {
+        (self.decks.is_empty() || self.decks.iter().any(|name| name.eq_ignore_ascii_case(&deck.name)))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| deck.has_tag(card, tag)))
+    }
+}
+
+pub struct Library {
+    pub decks: Vec<Deck>,
+    pickers: Vec<Picker>, // One per deck
+    filters: Vec<Filter>,
+    pinned: Option<usize>, // Deck chosen by middle-click, overrides the filters
+}
+
+impl Library {
+    pub fn load() -> Self {
+        let mut decks = deck::load_dir(utils::DECK_DIR);
+        if decks.is_empty() {
+            decks.push(deck::load(utils::DECK_FILES));
+        }
+        Library { filters: load_filters(utils::ACTIVE_DECKS_FILE), ..Library::new(decks) }
+    }
+
+    /// A library of `decks` without any filters, every deck active.
+    pub fn new(decks: Vec<Deck>) -> Self {
+        let pickers = decks.iter().map(|_| Picker::new()).collect();
+        Library { decks, pickers, filters: Vec::new(), pinned: None }
+    }
+
+    pub fn card(&self, id: CardId) -> &Card {
+        &self.decks[id.deck].cards[id.card]
+    }
+
+    /// Every card in every deck, for filling in readings and meanings.
+    pub fn cards_mut(&mut self) -> impl Iterator<Item = &mut Card> {
+        self.decks.iter_mut().flat_map(|deck| deck.cards.iter_mut())
+    }
+
+    // The cards of each deck that can be shown right now
+    fn active(&self, now: NaiveDateTime) -> Vec<Vec<usize>> {
+        let filter = self.filters.iter().find(|filter| filter.applies(now));
+        self.decks.iter().enumerate()
+            .map(|(index, deck)| {
+                if self.pinned.is_some_and(|pinned| pinned != index) {
+                    return Vec::new();
+                }
+                (0..deck.cards.len())
+                    .filter(|&card| self.pinned.is_some() || filter.is_none_or(|filter| filter.includes(deck, &deck.cards[card])))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Picks the next card from the active decks, or `None` if nothing is active. A deck
+    /// is chosen at random in proportion to how many active cards it has, then its own
+    /// picker chooses the card.
+    pub fn next(&mut self, reviews: &Reviews, now: u64, rng: &mut utils::SimpleRng) -> Option<CardId> {
+        let active = self.active(Local::now().naive_local());
+        let total: usize = active.iter().map(|cards| cards.len()).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut target = (rng.next_f32() * total as f32) as usize;
+        let mut deck = active.iter().rposition(|cards| !cards.is_empty())?;
+        for (index, cards) in active.iter().enumerate() {
+            if target < cards.len() {
+                deck = index;
+                break;
+            }
+            target -= cards.len();
+        }
+        let card = self.pickers[deck].next(&self.decks[deck], &active[deck], reviews, now, rng)?;
+        Some(CardId { deck, card })
+    }
+
+    /// A card chosen evenly from all the decks, whatever the filters and the decks' own
+    /// pickers, out of those `wanted` accepts, or out of all if it accepts none.
+    pub fn any(&self, wanted: impl Fn(&Card) -> bool, rng: &mut utils::SimpleRng) -> Option<CardId> {
+        let all: Vec<CardId> = self.decks.iter().enumerate()
+            .flat_map(|(index, deck)| (0..deck.cards.len()).map(move |card| CardId { deck: index, card }))
+            .collect();
+        let chosen: Vec<CardId> = all.iter().copied().filter(|id| wanted(self.card(*id))).collect();
+        let candidates = if chosen.is_empty() { all } else { chosen };
+        let index = (rng.next_f32() * candidates.len() as f32) as usize;
+        candidates.get(index.min(candidates.len().saturating_sub(1))).copied()
+    }
+
+    /// Pins the next deck (after the last one, goes back to the filters) and returns a
+    /// message saying what is active now.
+    pub fn switch(&mut self) -> String {
+        self.pinned = match self.pinned {
+            None if !self.decks.is_empty() => Some(0),
+            Some(index) if index + 1 < self.decks.len() => Some(index + 1),
+            _ => None,
+        };
+        match self.pinned {
+            Some(index) => format!("Deck: <b>{}</b>", self.decks[index].name),
+            None if self.filters.is_empty() => "All decks".to_string(),
+            None => "Decks by schedule".to_string(),
+        }
+    }
+}
+
+fn load_filters(path: &str) -> Vec<Filter> {
+    let Ok(content) = fs::read_to_string(path) else { return Vec::new() };
+    let file: FilterFile = match toml::from_str(&content) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    let mut filters = Vec::new();
+    for entry in file.filter {
+        let window = match entry.hours.as_deref() {
+            Some(hours) => Some(Window::parse(hours, &entry.days)),
+            None if !entry.days.is_empty() => Some(Window::all_day(&entry.days)),
+            None => None,
+        };
+        let window = match window.transpose() {
+            Ok(window) => window,
+            Err(e) => {
+                eprintln!("Skipping a filter in {}: {}", path, e);
+                continue;
+            }
+        };
+        filters.push(Filter { decks: entry.decks, tags: entry.tags, window });
+    }
+    filters
+}
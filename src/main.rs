@@ -1,5 +1,6 @@
 use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CYSCREEN};
-use std::{ffi::c_void, ptr::null_mut, thread, time::Duration, fs, io::Read};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyState, VK_CONTROL};
+use std::{ffi::c_void, ptr::null_mut, thread, time::Duration};
 use fontdue::{Font, FontSettings};
 use windows::{
     core::PCWSTR,
@@ -14,13 +15,31 @@ use windows::{
             CreateWindowExW, DefWindowProcW, RegisterClassW, ShowWindow, UpdateLayeredWindow, ULW_ALPHA,
             CS_HREDRAW, CS_VREDRAW, SW_SHOW, WNDCLASSW, WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_POPUP,
             PeekMessageW, TranslateMessage, DispatchMessageW, MSG, PM_REMOVE, WM_QUIT,
-            WM_LBUTTONDOWN, WM_LBUTTONUP, WM_RBUTTONDOWN, GetCursorPos,
+            WM_LBUTTONDOWN, WM_LBUTTONUP, WM_RBUTTONDOWN, WM_MBUTTONDOWN, GetCursorPos,
         },
     },
 };
 
 mod utils;
+mod activity;
+mod anki;
+mod bubble;
+mod daily;
+mod deck;
+mod dictionary;
+mod emoji;
+mod hours;
+mod kana;
+mod library;
+mod markup;
+mod picker;
+mod placement;
+mod quiz;
+mod review;
+mod schedule;
 mod scheduler;
+mod stats;
+mod text;
 
 fn to_wide(string: &str) -> Vec<u16> {
     use std::os::windows::ffi::OsStrExt;
@@ -45,6 +64,12 @@ unsafe extern "system" fn window_proc(
 
 
 fn main() {
+    // `parrot-pet --report` just writes the statistics exports and exits
+    if std::env::args().skip(1).any(|arg| arg == "--report") {
+        stats::Stats::load(utils::STATS_FILE).export();
+        return;
+    }
+
     unsafe {
         let screen_height = GetSystemMetrics(SM_CYSCREEN);
         
@@ -64,22 +89,53 @@ fn main() {
         let image_fly1_data = image_fly1.as_flat_samples().samples;
         let image_fly2_data = image_fly2.as_flat_samples().samples;
         let image_fly3_data = image_fly3.as_flat_samples().samples;
-        let image_bubble_data = image_bubble.as_flat_samples().samples;
 
-        // Load messages from file
-        let mut messages_file = fs::File::open("messages.txt").expect("messages.txt not found");
-        let mut messages_content = String::new();
-        messages_file.read_to_string(&mut messages_content).expect("Failed to read messages.txt");
-        let messages: Vec<String> = messages_content.lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.to_string())
-            .collect();
+        // Load the vocabulary decks (everything in decks/, or the single deck.toml/.json/.csv or plain messages.txt)
+        let mut library = library::Library::load();
+        // Look up readings and meanings for cards that are just a word
+        dictionary::enrich(library.cards_mut(), utils::DICTIONARY_FILES);
+        // Spaced repetition progress, used by the review picker and updated whatever the picker
+        let mut reviews = review::Reviews::load(utils::REVIEW_FILE);
+        // Impressions, answers and answer times, for the progress report
+        let mut stats = stats::Stats::load(utils::STATS_FILE);
+        // One card a day announced on the first right-click, see daily.rs
+        let mut word_of_the_day = daily::WordOfTheDay::load(utils::WORD_OF_THE_DAY_FILE, Box::new(scheduler::SystemClock));
+        // Reminders from reminders.txt, on the real clock, carrying on from the last run
+        let mut scheduler = scheduler::Scheduler::new(Box::new(scheduler::SystemClock), &scheduler::ReminderFile(utils::REMINDER_FILE.to_string()), utils::MISSED_REMINDERS);
+        scheduler.persist(utils::SCHEDULER_STATE_FILE);
+        // Quiet hours hold back reminders and put the parrot to sleep
+        if !utils::QUIET_HOURS.is_empty() {
+            let days: Vec<String> = utils::QUIET_DAYS.iter().map(|day| day.to_string()).collect();
+            match hours::Window::parse(utils::QUIET_HOURS, &days) {
+                Ok(window) => scheduler.quiet_hours(window, utils::QUIET_REMINDERS),
+                Err(e) => eprintln!("Invalid quiet hours, ignoring them: {}", e),
+            }
+        }
 
         // Load font for Japanese text rendering
         let font_data = std::fs::read("C:/Users/EPSY GREEN/AppData/Local/Microsoft/Windows/Fonts/NotoSansCJKjp-Regular.otf")
             .expect("Font not found at C:/Windows/Fonts/NotoSansCJKjp-Regular.otf. Please check the path and that the font is installed.");
         let font = Font::from_bytes(font_data, FontSettings::default()).expect("Failed to load font");
 
+        // Optional bold font for <b> markup, bold text falls back to the regular font without it
+        let bold_font = std::fs::read("C:/Users/EPSY GREEN/AppData/Local/Microsoft/Windows/Fonts/NotoSansCJKjp-Bold.otf")
+            .ok()
+            .and_then(|bold_data| match Font::from_bytes(bold_data, FontSettings::default()) {
+                Ok(bold_font) => Some(bold_font),
+                Err(e) => {
+                    eprintln!("Failed to load bold font: {}", e);
+                    None
+                }
+            });
+
+        // Optional colour emoji font (Segoe UI Emoji ships with Windows), emoji are skipped without it
+        let emoji_font = emoji::EmojiFont::load("C:/Windows/Fonts/seguiemj.ttf");
+        if emoji_font.is_none() {
+            eprintln!("Colour emoji font not found, emoji will not be drawn");
+        }
+
+        let fonts = text::Fonts { regular: font, bold: bold_font, emoji: emoji_font };
+
         // Simple text rendering without font loading for now
 
         // Register window class
@@ -130,9 +186,12 @@ fn main() {
         let scaled_bubble_h = bubble_h / utils::BUBBLE_SCALE;
         
         // Create a larger bitmap to hold both parrot and bubble
-        let vertical_padding = 200; // Space above parrot for bubble
-        let combined_width = scaled_w + scaled_bubble_w;
-        let combined_height = (scaled_h + vertical_padding).max(scaled_bubble_h + vertical_padding); // Add extra height for bubble positioning
+        // Leave a bubble's worth of room around the parrot so the bubble can go left, right or above it,
+        // or be pushed down alongside it near the top of the screen
+        let margin_x = scaled_bubble_w;
+        let margin_top = scaled_bubble_h;
+        let combined_width = scaled_w + 2 * margin_x;
+        let combined_height = scaled_h + 2 * margin_top;
         
         let bitmap_info = BITMAPINFO {
             bmiHeader: BITMAPINFOHEADER {
@@ -319,22 +378,51 @@ fn main() {
             }
         }
 
-        let mut bubble_bitmap_data: Vec<u8> = vec![0; (scaled_bubble_w * scaled_bubble_h * 4) as usize];
-        
-        // Scale bubble image
-        for y in 0..scaled_bubble_h {
-            for x in 0..scaled_bubble_w {
-                let src_x = (x * 4) as usize;
-                let src_y = (y * 4) as usize;
-                let src_idx = (src_y * bubble_w as usize + src_x) * 4;
-                let dest_idx = (y * scaled_bubble_w + x) as usize * 4;
-                
-                bubble_bitmap_data[dest_idx + 0] = image_bubble_data[src_idx + 2]; // B
-                bubble_bitmap_data[dest_idx + 1] = image_bubble_data[src_idx + 1]; // G
-                bubble_bitmap_data[dest_idx + 2] = image_bubble_data[src_idx + 0]; // R
-                bubble_bitmap_data[dest_idx + 3] = image_bubble_data[src_idx + 3]; // A
+        // Bubble art for every style, scaled and converted to BGRA, plus a mirrored copy for when
+        // the bubble sits to the right of the parrot. Styles without their own art use bubble.png.
+        let bubble_art: Vec<(Vec<u8>, Vec<u8>)> = bubble::BubbleStyle::ALL.iter().map(|style| {
+            let image = match image::open(style.asset()) {
+                Ok(image) => image.to_rgba8(),
+                Err(_) => {
+                    eprintln!("{} not found, using bubble.png instead", style.asset());
+                    image_bubble.clone()
+                }
+            };
+            // Every style has to line up with bubble.png's text area and tail
+            let image = if image.dimensions() != (bubble_w, bubble_h) {
+                image::imageops::resize(&image, bubble_w, bubble_h, image::imageops::FilterType::Triangle)
+            } else {
+                image
+            };
+            let image_data = image.as_flat_samples().samples;
+
+            let mut bitmap_data: Vec<u8> = vec![0; (scaled_bubble_w * scaled_bubble_h * 4) as usize];
+            for y in 0..scaled_bubble_h {
+                for x in 0..scaled_bubble_w {
+                    let src_x = (x * utils::BUBBLE_SCALE) as usize;
+                    let src_y = (y * utils::BUBBLE_SCALE) as usize;
+                    let src_idx = (src_y * bubble_w as usize + src_x) * 4;
+                    let dest_idx = (y * scaled_bubble_w + x) as usize * 4;
+                    // RGBA to premultiplied BGRA, some styles are partly see-through
+                    let alpha = image_data[src_idx + 3] as u32;
+                    bitmap_data[dest_idx + 0] = (image_data[src_idx + 2] as u32 * alpha / 255) as u8; // B
+                    bitmap_data[dest_idx + 1] = (image_data[src_idx + 1] as u32 * alpha / 255) as u8; // G
+                    bitmap_data[dest_idx + 2] = (image_data[src_idx + 0] as u32 * alpha / 255) as u8; // R
+                    bitmap_data[dest_idx + 3] = alpha as u8; // A
+                }
             }
-        }
+
+            let mut flipped_bits: Vec<u8> = vec![0; (scaled_bubble_w * scaled_bubble_h * 4) as usize];
+            for y in 0..scaled_bubble_h {
+                for x in 0..scaled_bubble_w {
+                    let src_idx = (y * scaled_bubble_w + x) as usize * 4;
+                    let flipped_x = scaled_bubble_w - 1 - x;
+                    let dest_idx = (y * scaled_bubble_w + flipped_x) as usize * 4;
+                    flipped_bits[dest_idx..dest_idx + 4].copy_from_slice(&bitmap_data[src_idx..src_idx + 4]);
+                }
+            }
+            (bitmap_data, flipped_bits)
+        }).collect();
 
         let mut pt_dst = POINT { x: 300, y: 300 };
         let pt_src = POINT { x: 0, y: 0 };
@@ -367,6 +455,7 @@ fn main() {
         let mut facing_right: bool = false; // Fixed: original sprite faces left
         let mut is_idle: bool = true;
         let mut idle_timer: u32 = 0;
+        let activity = activity::Activity::new(utils::ACTIVITY_PHASES); // Busy and calm phases, see activity.rs
         
         // Animation variables
         let mut animation_timer: u32 = 0;
@@ -388,13 +477,22 @@ fn main() {
         let mut is_dragging: bool = false;
         let mut drag_offset_x: i32 = 0;
         let mut drag_offset_y: i32 = 0;
+        let mut drag_start = POINT { x: 0, y: 0 }; // Cursor position when the left button went down
+        let mut left_click: Option<POINT> = None; // Where the left button was released without dragging
         
         // Speech bubble variables
         let mut show_bubble: bool = false;
         let mut last_show_bubble: bool = false;
-        let mut bubble_timer: u32 = 0;
-        let bubble_duration: u32 = 300; // Show bubble for 5 seconds (300 frames at 60fps)
-        let mut current_message: String = String::new();
+        let mut last_reminders_waiting: usize = 0; // Count shown on the parrot's badge
+        let mut last_sleeping: bool = false;
+        let mut bubble_timer: u32 = 0; // Frames since the bubble opened
+        let mut page_timer: u32 = 0; // Frames the current page has been shown
+        let mut current_pages: Vec<Vec<text::Line>> = Vec::new();
+        let mut current_page: usize = 0;
+        let mut current_style = utils::MESSAGE_BUBBLE_STYLE;
+        let mut current_card: Option<library::CardId> = None; // Card in the bubble, until it has been answered
+        let mut current_deck: usize = 0; // Deck of the last card shown, which the quiz's choices come from
+        let mut current_quiz: Option<quiz::Quiz> = None; // Question being asked about the card in quiz mode
         
         // Screen bounds
         let screen_width = GetSystemMetrics(windows::Win32::UI::WindowsAndMessaging::SM_CXSCREEN);
@@ -402,91 +500,130 @@ fn main() {
         // Get bitmap data pointer
         let dest = std::slice::from_raw_parts_mut(bits_ptr as *mut u8, (combined_width * combined_height * 4) as usize);
         
+        // Function to draw the bubble and its text into a bitmap at (bubble_x, bubble_y)
+        let draw_bubble = |dest: &mut [u8], style: bubble::BubbleStyle, pages: &[Vec<text::Line>], page_index: usize, typing_timer: u32, bubble_x: i32, bubble_y: i32, bubble_placement: placement::Placement| {
+            let art = &bubble_art[bubble::BubbleStyle::ALL.iter().position(|s| *s == style).unwrap_or(0)];
+            let bubble_data = if bubble_placement.mirrored { &art.1 } else { &art.0 };
+            for y in 0..scaled_bubble_h as i32 {
+                let dest_y = y + bubble_y;
+                if dest_y < 0 || dest_y >= combined_height as i32 {
+                    continue;
+                }
+                for x in 0..scaled_bubble_w as i32 {
+                    let dest_x = x + bubble_x;
+                    if dest_x < 0 || dest_x >= combined_width as i32 {
+                        continue;
+                    }
+                    let src_idx = (y as usize * scaled_bubble_w as usize + x as usize) * 4;
+                    let dest_idx = (dest_y as usize * combined_width as usize + dest_x as usize) * 4;
+                    dest[dest_idx..dest_idx + 4].copy_from_slice(&bubble_data[src_idx..src_idx + 4]);
+                }
+            }
+            // Render the current page of text in the bubble using fontdue
+            let (text_x, text_y) = bubble_placement.text_origin(scaled_bubble_w as i32);
+            let pen_x = bubble_x + text_x;
+            let pen_y = bubble_y + text_y;
+            if let Some(page) = pages.get(page_index) {
+                let glyphs = text::layout(&fonts, page, pen_x, pen_y, utils::BUBBLE_TEXT_WIDTH);
+                // Typewriter: only the glyphs revealed so far are drawn, the layout itself doesn't move
+                let visible = bubble::visible_glyphs(&glyphs, typing_timer);
+                text::draw(dest, combined_width as usize, combined_height as usize, &fonts, &glyphs[..visible]);
+            }
+            // Page indicator in the bottom right corner of the text area
+            if pages.len() > 1 {
+                let style = markup::Style { px: utils::PAGE_INDICATOR_SIZE, color: [128, 128, 128], ..Default::default() };
+                let label = text::Line::ltr(format!("{}/{}", page_index + 1, pages.len())
+                    .chars()
+                    .map(|ch| markup::StyledChar { ch, style })
+                    .collect());
+                let label_width = text::line_width(&fonts, &label.chars).ceil() as i32;
+                let label_x = pen_x + utils::BUBBLE_TEXT_WIDTH - label_width;
+                let label_y = pen_y + utils::BUBBLE_TEXT_HEIGHT;
+                let glyphs = text::layout(&fonts, &[label], label_x, label_y, label_width);
+                text::draw(dest, combined_width as usize, combined_height as usize, &fonts, &glyphs);
+            }
+        };
+
+        // Function to draw the number of waiting reminders in a badge on the parrot's top right corner
+        let draw_badge = |dest: &mut [u8], count: usize| {
+            let radius = utils::REMINDER_BADGE_SIZE as f32 / 2.0;
+            let center_x = (margin_x + scaled_w) as f32 - radius;
+            let center_y = margin_top as f32 + radius;
+            let [r, g, b] = utils::REMINDER_BADGE_COLOR;
+            for y in (center_y - radius).floor() as i32..=(center_y + radius).ceil() as i32 {
+                for x in (center_x - radius).floor() as i32..=(center_x + radius).ceil() as i32 {
+                    if x < 0 || x >= combined_width as i32 || y < 0 || y >= combined_height as i32 {
+                        continue;
+                    }
+                    // Anti-aliased edge, premultiplied over whatever is below
+                    let distance = ((x as f32 + 0.5 - center_x).powi(2) + (y as f32 + 0.5 - center_y).powi(2)).sqrt();
+                    let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                    if coverage == 0.0 {
+                        continue;
+                    }
+                    let idx = (y as usize * combined_width as usize + x as usize) * 4;
+                    for (channel, ink) in [b, g, r, 255].into_iter().enumerate() {
+                        dest[idx + channel] = (ink as f32 * coverage + dest[idx + channel] as f32 * (1.0 - coverage)).round() as u8;
+                    }
+                }
+            }
+            let style = markup::Style { px: radius * 1.4, bold: true, color: [255, 255, 255] };
+            let label = if count > 9 { "9+".to_string() } else { count.to_string() };
+            let label = text::Line::ltr(label.chars().map(|ch| markup::StyledChar { ch, style }).collect());
+            let label_width = text::line_width(&fonts, &label.chars);
+            let label_x = (center_x - label_width / 2.0).round() as i32;
+            let label_y = (center_y - style.px * 0.6).round() as i32;
+            let glyphs = text::layout(&fonts, &[label], label_x, label_y, label_width.ceil() as i32);
+            text::draw(dest, combined_width as usize, combined_height as usize, &fonts, &glyphs);
+        };
+
+        // Function to pick a message's bubble style and wrap it to the bubble's text area in pages
+        let paginate_message = |message: &str, default_style: bubble::BubbleStyle| -> (bubble::BubbleStyle, Vec<Vec<text::Line>>) {
+            let (style, message) = bubble::split_style(message);
+            let style = style.unwrap_or(default_style);
+            let styled = markup::parse(message, style.text_style());
+            let lines = text::wrap(&fonts, &styled, utils::BUBBLE_TEXT_WIDTH as f32);
+            (style, text::paginate(&fonts, lines, utils::BUBBLE_TEXT_HEIGHT as f32))
+        };
+
+        // Function to page a quiz prompt, keeping track of which lines belong to which answer
+        let paginate_prompt = |paragraphs: &[(String, Option<usize>)], style: bubble::BubbleStyle| -> Vec<Vec<text::Line>> {
+            let mut lines = Vec::new();
+            for (paragraph, region) in paragraphs {
+                let styled = markup::parse(paragraph, style.text_style());
+                for mut line in text::wrap(&fonts, &styled, utils::BUBBLE_TEXT_WIDTH as f32) {
+                    line.region = *region;
+                    lines.push(line);
+                }
+            }
+            text::paginate(&fonts, lines, utils::BUBBLE_TEXT_HEIGHT as f32)
+        };
+
         // Function to render combined image (parrot + optional bubble)
-        let render_combined_image = |dest: &mut [u8], parrot_data: &[u8], show_bubble: bool, message: &str| {
+        let render_combined_image = |dest: &mut [u8], parrot_data: &[u8], show_bubble: bool, style: bubble::BubbleStyle, pages: &[Vec<text::Line>], page_index: usize, bubble_frame: bubble::BubbleFrame, bubble_placement: placement::Placement, window_origin: POINT, reminders_waiting: usize, sleeping: bool| {
             // Clear the entire bitmap
             dest.fill(0);
             
             // Draw bubble first (behind) if showing
             if show_bubble {
-                let bubble_offset_y: i32 = utils::BUBBLE_OFFSET_Y;
-                let bubble_offset_x: usize = utils::BUBBLE_OFFSET_X as usize;
-                for y in 0..scaled_bubble_h {
-                    let dest_y_calc = y as i32 + bubble_offset_y;
-                    if dest_y_calc < 0 || dest_y_calc >= combined_height as i32 {
-                        continue;
-                    }
-                    let dest_y = dest_y_calc as usize;
-                    for x in 0..scaled_bubble_w as usize {
-                        let src_idx = (y as usize * scaled_bubble_w as usize + x) * 4;
-                        let dest_x = x + bubble_offset_x;
-                        if dest_x >= combined_width as usize {
-                            continue;
-                        }
-                        let dest_idx = (dest_y * combined_width as usize + dest_x) * 4;
-                        dest[dest_idx + 0] = bubble_bitmap_data[src_idx + 0];
-                        dest[dest_idx + 1] = bubble_bitmap_data[src_idx + 1];
-                        dest[dest_idx + 2] = bubble_bitmap_data[src_idx + 2];
-                        dest[dest_idx + 3] = bubble_bitmap_data[src_idx + 3];
-                    }
-                }
-                // Render text in bubble using fontdue
-                if !message.is_empty() {
-                    // Split message into words and wrap after every 4 words
-                    let words: Vec<&str> = message.split_whitespace().collect();
-                    let mut lines: Vec<String> = Vec::new();
-                    let mut i = 0;
-                    while i < words.len() {
-                        let end = (i + 2).min(words.len());
-                        let line = words[i..end].join(" ");
-                        lines.push(line);
-                        i += 2;
-                    }
-                    let mut pen_y = (bubble_offset_y + utils::BUBBLE_TEXT_START_Y).max(0) as i32;
-                    for (line_idx, line) in lines.iter().enumerate() {
-                        let mut pen_x = bubble_offset_x as i32 + utils::BUBBLE_TEXT_START_X;
-                        let mut char_count = 0;
-                        for ch in line.chars() {
-                            let font_size = if char_count < 2 { utils::FONT_SIZE_HEAD } else { utils::FONT_SIZE_MAIN };
-                            let (metrics, bitmap) = font.rasterize(ch, font_size);
-                            for y in 0..metrics.height {
-                                for x in 0..metrics.width {
-                                    let alpha = bitmap[y * metrics.width + x];
-                                    if alpha > 32 {
-                                        let dest_x = pen_x + x as i32;
-                                        let dest_y = pen_y + y as i32;
-                                        if dest_x >= 0 && dest_x < combined_width as i32 && dest_y >= 0 && dest_y < combined_height as i32 {
-                                            let idx = (dest_y as usize * combined_width as usize + dest_x as usize) * 4;
-                                            dest[idx + 0] = 0;
-                                            dest[idx + 1] = 0;
-                                            dest[idx + 2] = 0;
-                                            dest[idx + 3] = alpha;
-                                        }
-                                    }
-                                }
-                            }
-                            // Add extra spacing after CJK characters or dash
-                            if ch >= '\u{4E00}' && ch <= '\u{9FFF}' {
-                                pen_x += metrics.advance_width as i32 + 4; // CJK: add extra space
-                            } else if ch == '-' || ch == ' ' {
-                                pen_x += metrics.advance_width as i32 + 6; // dash/space: add more space
-                            } else {
-                                pen_x += metrics.advance_width as i32;
-                            }
-                            char_count += 1;
-                        }
-                        if line_idx == 0 {
-                            pen_y += utils::FIRST_LINE_SPACING;
-                        } else {
-                            pen_y += utils::OTHER_LINE_SPACING;
-                        }
-                    }
+                // Placement is in screen coordinates, the bitmap starts at the window origin
+                let bubble_x = bubble_placement.x - window_origin.x;
+                let bubble_y = bubble_placement.y - window_origin.y;
+                if bubble_frame.is_transitioning() {
+                    // Popping in or fading out: draw into a separate layer, then scale it around the tail
+                    let mut layer = vec![0u8; dest.len()];
+                    draw_bubble(&mut layer, style, pages, page_index, bubble_frame.timer, bubble_x, bubble_y, bubble_placement);
+                    let (tail_x, tail_y) = bubble_placement.tail(scaled_bubble_w as i32, scaled_bubble_h as i32);
+                    let anchor = ((bubble_x + tail_x) as f32, (bubble_y + tail_y) as f32);
+                    bubble::composite(dest, &layer, combined_width as usize, combined_height as usize, anchor, bubble_frame.scale, bubble_frame.opacity);
+                } else {
+                    draw_bubble(dest, style, pages, page_index, bubble_frame.timer, bubble_x, bubble_y, bubble_placement);
                 }
             }
             
             // Draw parrot on top (in front of bubble)
-            let parrot_y_offset = vertical_padding as usize;
-            let parrot_x_offset = scaled_bubble_w as usize;
+            let parrot_y_offset = margin_top as usize;
+            let parrot_x_offset = margin_x as usize;
             for y in 0..scaled_h as usize {
                 for x in 0..scaled_w as usize {
                     let src_idx = (y * scaled_w as usize + x) * 4;
@@ -497,19 +634,84 @@ fn main() {
                     dest[dest_idx + 3] = parrot_data[src_idx + 3]; // A
                 }
             }
-            
+
+            if sleeping {
+                // Snoring above the parrot's head
+                let style = markup::Style { px: utils::PAGE_INDICATOR_SIZE * 1.5, color: [128, 128, 128], ..Default::default() };
+                let label = text::Line::ltr(utils::NIGHT_SNORE.chars().map(|ch| markup::StyledChar { ch, style }).collect());
+                let label_width = text::line_width(&fonts, &label.chars).ceil() as i32;
+                let label_x = margin_x as i32 + scaled_w as i32 / 2;
+                let label_y = margin_top as i32 - style.px as i32;
+                let glyphs = text::layout(&fonts, &[label], label_x, label_y, label_width);
+                text::draw(dest, combined_width as usize, combined_height as usize, &fonts, &glyphs);
+            }
+
+            if reminders_waiting > 0 {
+                draw_badge(dest, reminders_waiting);
+            }
 
         };
         
         // Initialize with normal frame using combined rendering
-        render_combined_image(dest, &normal_bitmap_data, false, "");
+        let initial_placement = placement::place(pt_dst.x, pt_dst.y, scaled_w as i32, scaled_bubble_w as i32, scaled_bubble_h as i32, screen_width, screen_height);
+        render_combined_image(dest, &normal_bitmap_data, false, utils::MESSAGE_BUBBLE_STYLE, &[], 0, bubble::BubbleFrame::at(0, 0, 0, true), initial_placement, pt_dst, 0, false);
         
         loop {
+            // A left click while a quiz question is up answers it (or just reveals the card),
+            // on a card that is already showing it means the user knew it (and closes it, as
+            // it does a revealed quiz answer), and on a reminder it snoozes it or marks it done
+            // (handled at the top of the frame so the page timings below are up to date)
+            if let Some(click) = left_click.take() && show_bubble {
+                // Which answer or action, if any, was clicked
+                let bubble_placement = placement::place(position_x as i32, position_y as i32, scaled_w as i32, scaled_bubble_w as i32, scaled_bubble_h as i32, screen_width, screen_height);
+                let (text_x, text_y) = bubble_placement.text_origin(scaled_bubble_w as i32);
+                let text_x = bubble_placement.x + text_x;
+                let text_y = bubble_placement.y + text_y;
+                let region = current_pages.get(current_page)
+                    .filter(|_| click.x >= text_x && click.x < text_x + utils::BUBBLE_TEXT_WIDTH)
+                    .and_then(|page| text::line_at(&fonts, page, (click.y - text_y) as f32))
+                    .and_then(|line| line.region);
+                if let Some(quiz) = current_quiz.as_mut().filter(|quiz| !quiz.revealed) {
+                    if let Some(choice) = region {
+                        let grade = quiz.pick(choice);
+                        if let Some(id) = current_card.take() {
+                            reviews.grade(library.card(id), grade, review::now());
+                            stats.answered(library.card(id), grade);
+                        }
+                    }
+                    // Clicking anywhere else just shows the answer, to be marked as usual
+                    quiz.revealed = true;
+                    current_pages = paginate_message(&quiz.answer(&library.decks[current_deck].cards), current_style).1;
+                    current_page = 0;
+                    page_timer = 0;
+                } else if current_card.is_some() || current_quiz.is_some() || scheduler.is_showing() {
+                    if let Some(id) = current_card.take() {
+                        reviews.grade(library.card(id), review::Grade::Good, review::now());
+                        stats.answered(library.card(id), review::Grade::Good);
+                    } else if scheduler.is_showing() {
+                        // Clicking the reminder itself rather than an action counts as done
+                        let action = region.and_then(|position| scheduler::Action::ALL.get(position)).copied();
+                        scheduler.answer(action.unwrap_or(scheduler::Action::Done));
+                    }
+                    // A quiz that has been answered and revealed is simply done with
+                    current_quiz = None;
+                    // Close the bubble: jump to the fade-out of its last page
+                    current_page = current_pages.len().saturating_sub(1);
+                    let last_duration = current_pages.last().map_or(0, |page| bubble::page_duration(page));
+                    page_timer = last_duration.saturating_sub(utils::BUBBLE_FADE_FRAMES);
+                }
+            }
+
+            // How long the current bubble page stays up, based on how much there is to read
+            // (and for a quiz question or a reminder's actions, long enough to think about it)
+            let asking = current_quiz.as_ref().is_some_and(|quiz| !quiz.revealed) || scheduler.is_showing();
+            let (page_duration, last_page) = bubble::page_timing(&current_pages, current_page, asking);
+
             // Scheduler tick: check if a reminder should be queued
-            scheduler::tick();
+            scheduler.tick();
 
             // If a reminder is ready, make the parrot jump to signal
-            if scheduler::has_message_ready() && !is_dragging && !show_bubble && position_y >= (screen_height as f32 - scaled_h as f32 - 1.0) {
+            if scheduler.has_message_ready() && !is_dragging && !show_bubble && position_y >= (screen_height as f32 - scaled_h as f32 - 1.0) {
                 // Simulate a jump by setting upward velocity
                 velocity_y = -12.0;
             }
@@ -518,6 +720,7 @@ fn main() {
             while PeekMessageW(&mut msg, hwnd, 0, 0, PM_REMOVE).as_bool() {
                 match msg.message {
                     WM_QUIT => {
+                        stats.export();
                         // Cleanup resources before exiting
                         SelectObject(mem_dc, old_bitmap);
                         let _ = DeleteObject(h_bitmap);
@@ -538,8 +741,9 @@ fn main() {
                         if cursor_pos.x >= parrot_left && cursor_pos.x <= parrot_right &&
                            cursor_pos.y >= parrot_top && cursor_pos.y <= parrot_bottom {
                             is_dragging = true;
-                            drag_offset_x = cursor_pos.x - pt_dst.x;
-                            drag_offset_y = cursor_pos.y - pt_dst.y;
+                            drag_start = cursor_pos;
+                            drag_offset_x = cursor_pos.x - position_x as i32;
+                            drag_offset_y = cursor_pos.y - position_y as i32;
                             // Stop physics when dragging
                             velocity_x = 0.0;
                             velocity_y = 0.0;
@@ -550,6 +754,12 @@ fn main() {
                     WM_LBUTTONUP => {
                         if is_dragging {
                             is_dragging = false;
+                            let mut cursor_pos = POINT { x: 0, y: 0 };
+                            let _ = GetCursorPos(&mut cursor_pos);
+                            if (cursor_pos.x - drag_start.x).abs() < utils::CLICK_DISTANCE
+                                && (cursor_pos.y - drag_start.y).abs() < utils::CLICK_DISTANCE {
+                                left_click = Some(cursor_pos);
+                            }
                             // Reset timers when dropped
                             movement_timer = 0;
                             idle_timer = 0;
@@ -560,6 +770,24 @@ fn main() {
                             fly_animation_timer = 0;
                         }
                     }
+                    WM_MBUTTONDOWN => {
+                        // Middle-click pins the next deck and says which one is active
+                        let mut cursor_pos = POINT { x: 0, y: 0 };
+                        let _ = GetCursorPos(&mut cursor_pos);
+
+                        if cursor_pos.x >= pt_dst.x && cursor_pos.x <= pt_dst.x + combined_width as i32 &&
+                           cursor_pos.y >= pt_dst.y && cursor_pos.y <= pt_dst.y + combined_height as i32 {
+                            let message = library.switch();
+                            scheduler.dismiss();
+                            show_bubble = true;
+                            bubble_timer = 0;
+                            page_timer = 0;
+                            (current_style, current_pages) = paginate_message(&message, utils::MESSAGE_BUBBLE_STYLE);
+                            current_card = None;
+                            current_quiz = None;
+                            current_page = 0;
+                        }
+                    }
                     WM_RBUTTONDOWN => {
                         // Check if right-click is within parrot bounds
                         let mut cursor_pos = POINT { x: 0, y: 0 };
@@ -573,11 +801,18 @@ fn main() {
                         if cursor_pos.x >= parrot_left && cursor_pos.x <= parrot_right &&
                            cursor_pos.y >= parrot_top && cursor_pos.y <= parrot_bottom {
                             // Show reminder if available, else fallback to random message
-                            if scheduler::has_message_ready() {
-                                if let Some(reminder) = scheduler::get_message() {
+                            if scheduler.has_message_ready() {
+                                if let Some(reminder) = scheduler.get_message() {
                                     show_bubble = true;
                                     bubble_timer = 0;
-                                    current_message = reminder;
+                                    page_timer = 0;
+                                    // The reminder with its snooze/done actions below it
+                                    let (style, reminder) = bubble::split_style(&reminder);
+                                    current_style = style.unwrap_or(utils::REMINDER_BUBBLE_STYLE);
+                                    current_pages = paginate_prompt(&scheduler::prompt(reminder), current_style);
+                                    current_card = None;
+                                    current_quiz = None;
+                                    current_page = 0;
                                     velocity_x = 0.0;
                                     velocity_y = 0.0;
                                     target_velocity_x = 0.0;
@@ -588,12 +823,65 @@ fn main() {
                                     fly_animation_timer = 0;
                                 }
                             } else {
-                                // Fallback to random message
-                                show_bubble = !show_bubble;
-                                bubble_timer = 0;
-                                if show_bubble {
-                                    let random_index = (rng.next_f32() * messages.len() as f32) as usize;
-                                    current_message = messages[random_index].clone();
+                                // The first right-click of the day announces the word of the day,
+                                // Ctrl+right-click shows it again
+                                let ctrl = GetKeyState(VK_CONTROL.0 as i32) < 0;
+                                let announce = !show_bubble && word_of_the_day.due();
+                                let daily_card = if ctrl || announce { word_of_the_day.card(&library) } else { None };
+                                if let Some(id) = daily_card {
+                                    // Only counts as announced once there was a card to show
+                                    word_of_the_day.announced();
+                                    let card = library.card(id);
+                                    stats.shown(card, &library.decks[id.deck].name);
+                                    scheduler.dismiss();
+                                    show_bubble = true;
+                                    bubble_timer = 0;
+                                    page_timer = 0;
+                                    (current_style, current_pages) = paginate_message(&daily::message(&card.message()), utils::MESSAGE_BUBBLE_STYLE);
+                                    current_card = Some(id);
+                                    current_deck = id.deck;
+                                    current_quiz = None;
+                                    current_page = 0;
+                                } else if show_bubble && !last_page {
+                                    // Turn to the next page
+                                    current_page += 1;
+                                    page_timer = 0;
+                                } else if show_bubble {
+                                    // Already on the last page: the user didn't know this card
+                                    if let Some(id) = current_card.take() {
+                                        reviews.grade(library.card(id), review::Grade::Again, review::now());
+                                        stats.answered(library.card(id), review::Grade::Again);
+                                    }
+                                    if let Some(quiz) = current_quiz.as_mut().filter(|quiz| !quiz.revealed) {
+                                        // Gave up on the question, show the answer
+                                        quiz.revealed = true;
+                                        current_pages = paginate_message(&quiz.answer(&library.decks[current_deck].cards), current_style).1;
+                                        current_page = 0;
+                                        page_timer = 0;
+                                    } else {
+                                        // Skip ahead to the fade-out instead of vanishing
+                                        page_timer = page_timer.max(page_duration.saturating_sub(utils::BUBBLE_FADE_FRAMES));
+                                    }
+                                } else if let Some(id) = library.next(&reviews, review::now(), &mut rng) {
+                                    let cards = &library.decks[id.deck].cards;
+                                    stats.shown(&cards[id.card], &library.decks[id.deck].name);
+                                    scheduler.dismiss();
+                                    show_bubble = true;
+                                    bubble_timer = 0;
+                                    page_timer = 0;
+                                    if utils::QUIZ_MODE {
+                                        // Ask first, the rest of the card shows once answered
+                                        let quiz = quiz::Quiz::new(cards, id.card, &mut rng);
+                                        current_style = utils::MESSAGE_BUBBLE_STYLE;
+                                        current_pages = paginate_prompt(&quiz.prompt(cards), current_style);
+                                        current_quiz = Some(quiz);
+                                    } else {
+                                        (current_style, current_pages) = paginate_message(&cards[id.card].message(), utils::MESSAGE_BUBBLE_STYLE);
+                                        current_quiz = None;
+                                    }
+                                    current_card = Some(id);
+                                    current_deck = id.deck;
+                                    current_page = 0;
                                     velocity_x = 0.0;
                                     velocity_y = 0.0;
                                     target_velocity_x = 0.0;
@@ -621,7 +909,7 @@ fn main() {
                 let new_y = cursor_pos.y - drag_offset_y;
                 
                 // Keep within screen bounds
-                let screen_right = screen_width - combined_width as i32;
+                let screen_right = screen_width - scaled_w as i32;
                 let screen_bottom = screen_height - scaled_h as i32;
                 
                 position_x = new_x.max(0).min(screen_right) as f32;
@@ -635,6 +923,10 @@ fn main() {
                 // If left button is not pressed, stop dragging
                 if (left_button_state & -0x8000) == 0 {
                     is_dragging = false;
+                    if (cursor_pos.x - drag_start.x).abs() < utils::CLICK_DISTANCE
+                        && (cursor_pos.y - drag_start.y).abs() < utils::CLICK_DISTANCE {
+                        left_click = Some(cursor_pos);
+                    }
                     movement_timer = 0;
                     idle_timer = 0;
                     // Start flying animation
@@ -645,15 +937,30 @@ fn main() {
                 }
             }
 
-            // Handle speech bubble timer
+            // Handle speech bubble timer, for the bubble as the clicks above left it
+            let asking = current_quiz.as_ref().is_some_and(|quiz| !quiz.revealed) || scheduler.is_showing();
+            let (page_duration, last_page) = bubble::page_timing(&current_pages, current_page, asking);
             if show_bubble {
                 bubble_timer += 1;
-                if bubble_timer >= bubble_duration {
-                    show_bubble = false;
-                    bubble_timer = 0;
+                page_timer += 1;
+                if page_timer >= page_duration {
+                    page_timer = 0;
+                    if last_page {
+                        show_bubble = false;
+                        bubble_timer = 0;
+                        // Timed out without an answer, the card stays due
+                        current_card = None;
+                        current_quiz = None;
+                        scheduler.dismiss();
+                    } else {
+                        current_page += 1;
+                    }
                 }
             }
             
+            // Night mode during quiet hours: less movement and a sleeping pose
+            let night = scheduler.is_quiet();
+
             // Only do physics and movement if not being dragged and no bubble is shown
             if !is_dragging && !show_bubble {
                 // Fixed random movement system
@@ -661,14 +968,23 @@ fn main() {
                 idle_timer += 1;
                 
                 if is_idle {
+                    // How restless the parrot is in the activity cycle right now, and less so asleep at night
+                    let mut energy = activity.energy();
+                    if night {
+                        energy.move_chance *= utils::NIGHT_MOVE_CHANCE;
+                        energy.speed *= utils::NIGHT_SPEED;
+                    }
                     // In idle state - randomly decide to start moving
-                    let idle_duration = 180 + (rng.next_f32() * 600.0) as u32; // Random idle time 180-780 frames (3-13 seconds)
-                    if idle_timer > idle_duration {
+                    let idle_duration = energy.idle_frames(rng.next_f32()); // Random idle time in the phase's range
+                    if idle_timer > idle_duration && rng.next_f32() >= energy.move_chance {
+                        // Not in the mood, sit a while longer
+                        idle_timer = 0;
+                    } else if idle_timer > idle_duration {
                         is_idle = false;
                         idle_timer = 0;
                         movement_timer = 0;
                         // Generate new random horizontal velocity and duration
-                        let speed_multiplier = 0.5 + rng.next_f32() * 2.5; // Random speed 0.5-3.0
+                        let speed_multiplier = (0.5 + rng.next_f32() * 2.5) * energy.speed; // Random speed 0.5-3.0, scaled by the phase
                         let direction = if rng.next_f32() > 0.5 { 1.0 } else { -1.0 }; // 50/50 chance
                         target_velocity_x = direction * speed_multiplier;
                     }
@@ -692,7 +1008,7 @@ fn main() {
                 position_x += velocity_x;
 
                 let screen_bottom = screen_height as f32 - scaled_h as f32;
-                let screen_right = screen_width as f32 - combined_width as f32;
+                let screen_right = screen_width as f32 - scaled_w as f32;
 
                 // Floor collision
                 if position_y >= screen_bottom {
@@ -731,6 +1047,13 @@ fn main() {
                     fly_animation_timer = 0;
                 }
             }
+            // At night the idle parrot sleeps, head down, instead of bobbing
+            else if is_idle && night {
+                use_low_frame = true;
+                animation_timer = 0;
+                is_animating = false;
+                animation_check_timer = 0;
+            }
             // Handle idle animation (only if not flying)
             else if is_idle {
                 animation_check_timer += 1;
@@ -767,12 +1090,28 @@ fn main() {
             
             // Update facing direction
             let new_facing_right = velocity_x > 0.1;
-            let need_update = new_facing_right != facing_right || use_low_frame != last_animation_frame || is_flying || show_bubble != last_show_bubble;
+            let sleeping = night && is_idle && !is_flying && !show_bubble;
+            // Keep redrawing while the bubble pops in, fades out or types its text
+            let asking = current_quiz.as_ref().is_some_and(|quiz| !quiz.revealed) || scheduler.is_showing();
+            let (page_duration, last_page) = bubble::page_timing(&current_pages, current_page, asking);
+            let bubble_frame = bubble::BubbleFrame::at(bubble_timer, page_timer, page_duration, last_page);
+            let typing = current_pages.get(current_page)
+                .is_some_and(|page| page_timer <= bubble::typing_frames(page.iter().flat_map(|line| &line.chars).map(|c| c.ch)));
+            // Page turns need a redraw too, the typewriter covers that since it restarts at 0
+            let bubble_animating = show_bubble && (bubble_frame.is_transitioning() || typing);
+            let new_y = position_y.round() as i32;
+            let new_x = position_x.round() as i32;
+            // The bubble is placed against the screen edges, so it has to be redrawn whenever the parrot moves
+            let bubble_moved = show_bubble && (new_x != last_drawn_x || new_y != last_drawn_y);
+            let need_update = new_facing_right != facing_right || use_low_frame != last_animation_frame || is_flying || show_bubble != last_show_bubble || bubble_animating || bubble_moved
+                || scheduler.pending_count() != last_reminders_waiting || sleeping != last_sleeping;
             
             if need_update {
                 facing_right = new_facing_right;
                 last_animation_frame = use_low_frame;
                 last_show_bubble = show_bubble;
+                last_reminders_waiting = scheduler.pending_count();
+                last_sleeping = sleeping;
                 
                 // Determine which parrot image to use
                 let parrot_data = if is_flying {
@@ -817,15 +1156,15 @@ fn main() {
                 };
                 
                 // Render combined image
-                render_combined_image(dest, parrot_data, show_bubble, &current_message);
+                let window_origin = POINT { x: new_x - margin_x as i32, y: new_y - margin_top as i32 };
+                let bubble_placement = placement::place(new_x, new_y, scaled_w as i32, scaled_bubble_w as i32, scaled_bubble_h as i32, screen_width, screen_height);
+                render_combined_image(dest, parrot_data, show_bubble, current_style, &current_pages, current_page, bubble_frame, bubble_placement, window_origin, scheduler.pending_count(), sleeping);
             }
 
-            let new_y = position_y.round() as i32;
-            let new_x = position_x.round() as i32;
-
             if new_y != last_drawn_y || new_x != last_drawn_x || need_update {
-                pt_dst.y = new_y - vertical_padding as i32;
-                pt_dst.x = new_x;
+                // The window extends past the parrot by the bubble margins
+                pt_dst.y = new_y - margin_top as i32;
+                pt_dst.x = new_x - margin_x as i32;
                 last_drawn_y = new_y;
                 last_drawn_x = new_x;
 
@@ -0,0 +1,140 @@
+use crate::utils;
+
+// Lightweight rich-text markup for messages, e.g. `<b>夢</b> - <c=#c00>Dream</c>`.
+// Supported tags:
+//   <b>...</b>          bold (drawn with the bold font)
+//   <c=#rgb>...</c>     colour, also accepts #rrggbb
+//   <s=25>...</s>       font size in pixels, a bare <s> uses FONT_SIZE_HEAD
+//   <br>                line break
+// Anything that isn't a recognised tag is drawn as plain text. `&lt;`, `&gt;` and `&amp;`
+// draw `<`, `>` and `&` for text that must not be read as a tag (see `escape`).
+
+/// How a run of text should be drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Style {
+    pub px: f32,
+    pub bold: bool,
+    pub color: [u8; 3], // RGB
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            px: utils::FONT_SIZE_MAIN,
+            bold: false,
+            color: [0, 0, 0],
+        }
+    }
+}
+
+/// A single character together with the style it should be drawn in.
+#[derive(Clone, Copy, Debug)]
+pub struct StyledChar {
+    pub ch: char,
+    pub style: Style,
+}
+
+enum Tag {
+    Open(char, Style),
+    Close(char),
+    LineBreak,
+}
+
+/// Parses a message into styled characters, starting from the `base` style.
+/// Line breaks come out as `'\n'`.
+pub fn parse(message: &str, base: Style) -> Vec<StyledChar> {
+    let mut out = Vec::new();
+    // Each open tag pushes the style it produces, closing pops back to the enclosing one
+    let mut stack: Vec<(char, Style)> = vec![(' ', base)];
+    let mut rest = message;
+    while let Some(ch) = rest.chars().next() {
+        let current = stack.last().unwrap().1;
+        if ch == '<'
+            && let Some(end) = rest.find('>')
+            && let Some(tag) = parse_tag(&rest[1..end], current)
+        {
+            match tag {
+                Tag::Open(kind, style) => stack.push((kind, style)),
+                Tag::Close(kind) => {
+                    // Ignore stray closing tags rather than unwinding unrelated styles
+                    if stack.len() > 1 && stack.last().unwrap().0 == kind {
+                        stack.pop();
+                    }
+                }
+                Tag::LineBreak => out.push(StyledChar { ch: '\n', style: current }),
+            }
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if ch == '&'
+            && let Some((entity, literal)) = ESCAPES.iter().find(|(entity, _)| rest.starts_with(entity))
+        {
+            out.push(StyledChar { ch: *literal, style: current });
+            rest = &rest[entity.len()..];
+            continue;
+        }
+        out.push(StyledChar { ch, style: current });
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
+
+// What `parse` reads as a literal character rather than markup
+const ESCAPES: [(&str, char); 3] = [("&lt;", '<'), ("&gt;", '>'), ("&amp;", '&')];
+
+/// Escapes `text` so `parse` draws it as it is, tags and all.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn parse_tag(tag: &str, current: Style) -> Option<Tag> {
+    let tag = tag.trim();
+    match tag {
+        "br" | "br/" | "br /" => return Some(Tag::LineBreak),
+        "b" => return Some(Tag::Open('b', Style { bold: true, ..current })),
+        "s" => return Some(Tag::Open('s', Style { px: utils::FONT_SIZE_HEAD, ..current })),
+        "/b" => return Some(Tag::Close('b')),
+        "/c" => return Some(Tag::Close('c')),
+        "/s" => return Some(Tag::Close('s')),
+        _ => {}
+    }
+    let (name, value) = tag.split_once('=')?;
+    match name.trim() {
+        "c" => parse_color(value.trim()).map(|color| Tag::Open('c', Style { color, ..current })),
+        "s" => value.trim().parse::<f32>().ok()
+            .filter(|px| *px > 0.0)
+            .map(|px| Tag::Open('s', Style { px, ..current })),
+        _ => None,
+    }
+}
+
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+    let hex_at = |i: usize, len: usize| u8::from_str_radix(hex.get(i..i + len)?, 16).ok();
+    match hex.len() {
+        // #rgb: each digit is doubled, so #c00 == #cc0000
+        3 => Some([hex_at(0, 1)? * 17, hex_at(1, 1)? * 17, hex_at(2, 1)? * 17]),
+        6 => Some([hex_at(0, 2)?, hex_at(2, 2)?, hex_at(4, 2)?]),
+        _ => None,
+    }
+}
+
+/// Decodes an HTML/XML character reference such as `amp` or `#x3042` (without the `&` and `;`).
+pub fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "nbsp" => Some(' '),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
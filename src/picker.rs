@@ -0,0 +1,176 @@
+use crate::deck::Deck;
+use crate::review::Reviews;
+use crate::utils;
+
+// Decides which card the parrot shows next. Each deck picks one strategy:
+//   review        spaced repetition, whatever is due first (see review.rs)
+//   shuffle       shuffle-bag: every card once in random order, then a new shuffle
+//   weighted      random, with cards more likely the heavier their tags weigh
+//   least-recent  the card that hasn't been shown for the longest
+// Whatever the strategy, the same card is never shown twice in a row unless it is the
+// only one, and an empty deck simply has nothing to show.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strategy {
+    Review,
+    Shuffle,
+    Weighted,
+    LeastRecent,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "review" | "srs" | "sm2" => Some(Strategy::Review),
+            "shuffle" | "shuffle-bag" => Some(Strategy::Shuffle),
+            "weighted" | "weighted-by-tag" => Some(Strategy::Weighted),
+            "least-recent" | "least-recently-shown" => Some(Strategy::LeastRecent),
+            _ => None,
+        }
+    }
+}
+
+pub struct Picker {
+    bag: Vec<usize>,               // Cards left in the current shuffle
+    shown_at: Vec<Option<u64>>,    // When each card was last shown, counted in picks
+    picks: u64,
+    last: Option<usize>,
+}
+
+impl Picker {
+    pub fn new() -> Self {
+        Picker { bag: Vec::new(), shown_at: Vec::new(), picks: 0, last: None }
+    }
+
+    /// Picks the next card in `deck` out of the `active` ones, or `None` if there are none.
+    pub fn next(&mut self, deck: &Deck, active: &[usize], reviews: &Reviews, now: u64, rng: &mut utils::SimpleRng) -> Option<usize> {
+        let count = deck.cards.len();
+        // The deck may have changed size since the last pick
+        self.shown_at.resize(count, None);
+        let mut candidates: Vec<usize> = active.iter().copied().filter(|&index| index < count).collect();
+        if candidates.len() > 1 {
+            candidates.retain(|&index| Some(index) != self.last);
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let index = match deck.picker {
+            Strategy::Review => reviews.next_card(&deck.cards, &candidates, now)?,
+            Strategy::Shuffle => {
+                // Cards that are no longer active drop out of the bag
+                self.bag.retain(|index| active.contains(index));
+                if self.bag.is_empty() {
+                    self.bag = active.iter().copied().filter(|&index| index < count).collect();
+                    rng.shuffle(&mut self.bag);
+                }
+                // Don't let a new round start with the card that ended the last one
+                if self.bag.len() > 1
+                    && let Some(last) = self.last
+                    && self.bag.last() == Some(&last)
+                {
+                    let end = self.bag.len() - 1;
+                    self.bag.swap(0, end);
+                }
+                self.bag.pop()?
+            }
+            Strategy::Weighted => {
+                let weights: Vec<f32> = candidates.iter().map(|&index| deck.weight(&deck.cards[index]).max(0.0)).collect();
+                let total: f32 = weights.iter().sum();
+                if total <= 0.0 {
+                    // Every weight is zero, fall back to an even choice
+                    candidates[(rng.next_f32() * candidates.len() as f32) as usize % candidates.len()]
+                } else {
+                    let mut target = rng.next_f32() * total;
+                    let mut chosen = candidates.len() - 1;
+                    for (position, weight) in weights.iter().enumerate() {
+                        if *weight > 0.0 && target < *weight {
+                            chosen = position;
+                            break;
+                        }
+                        target -= weight;
+                    }
+                    // Rounding can leave the target just past the end, take the last card that can be picked
+                    if weights[chosen] <= 0.0 {
+                        chosen = weights.iter().rposition(|weight| *weight > 0.0)?;
+                    }
+                    candidates[chosen]
+                }
+            }
+            // Never shown sorts before any pick number
+            Strategy::LeastRecent => candidates.iter().copied().min_by_key(|&index| self.shown_at[index])?,
+        };
+
+        self.picks += 1;
+        self.shown_at[index] = Some(self.picks);
+        self.last = Some(index);
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::deck::Card;
+
+    // A deck of `count` cards named by number, the first `zero` tagged to weigh nothing
+    fn deck(strategy: Strategy, count: usize, zero: usize) -> Deck {
+        let cards = (0..count)
+            .map(|index| {
+                let mut card = Card::legacy(&index.to_string());
+                if index < zero {
+                    card.tags = vec!["never".to_string()];
+                }
+                card
+            })
+            .collect();
+        let tag_weights = HashMap::from([("never".to_string(), 0.0)]);
+        Deck { name: "test".to_string(), cards, tags: Vec::new(), picker: strategy, tag_weights }
+    }
+
+    // Picks `picks` cards in a row from every card in `deck`
+    fn picks(deck: &Deck, picks: usize) -> Vec<usize> {
+        let mut picker = Picker::new();
+        let reviews = Reviews::load("");
+        let active: Vec<usize> = (0..deck.cards.len()).collect();
+        let mut rng = utils::SimpleRng::with_seed(7);
+        (0..picks).map(|_| picker.next(deck, &active, &reviews, 0, &mut rng).unwrap()).collect()
+    }
+
+    #[test]
+    fn shuffle_shows_every_card_once_a_round() {
+        let shown = picks(&deck(Strategy::Shuffle, 5, 0), 5 * 20);
+        for round in shown.chunks(5) {
+            let mut round = round.to_vec();
+            round.sort();
+            assert_eq!(round, [0, 1, 2, 3, 4]);
+        }
+        // Not even a new round starts with the card that was just shown
+        assert!(shown.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn weighted_never_picks_a_card_that_weighs_nothing() {
+        let shown = picks(&deck(Strategy::Weighted, 6, 3), 500);
+        assert!(shown.iter().all(|&index| index >= 3));
+        // The rest all turn up
+        assert!((3..6).all(|index| shown.contains(&index)));
+    }
+
+    #[test]
+    fn least_recent_picks_the_oldest_card() {
+        let deck = deck(Strategy::LeastRecent, 3, 0);
+        let mut picker = Picker::new();
+        let reviews = Reviews::load("");
+        let mut rng = utils::SimpleRng::new();
+        let mut next = |active: &[usize]| picker.next(&deck, active, &reviews, 0, &mut rng);
+        // Cards never shown come first, then the one shown longest ago
+        assert_eq!([next(&[0, 1, 2]), next(&[0, 1, 2]), next(&[0, 1, 2])], [Some(0), Some(1), Some(2)]);
+        assert_eq!(next(&[0, 1, 2]), Some(0));
+        assert_eq!(next(&[1, 2]), Some(1));
+        assert_eq!(next(&[0, 2]), Some(2));
+        assert_eq!(next(&[]), None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::utils;
+
+// Decides where the speech bubble goes relative to the parrot so that it stays on screen.
+// The bubble art has its tail in the bottom right corner; when the bubble ends up to the
+// right of the parrot the art is mirrored so the tail still points at it.
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+    Above,
+}
+
+/// Where to draw the bubble, in screen coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Placement {
+    pub x: i32, // Left edge of the bubble
+    pub y: i32, // Top edge of the bubble
+    pub mirrored: bool, // Tail in the bottom left corner instead of bottom right
+}
+
+impl Placement {
+    /// The point the tail sits at, relative to the bubble's top left corner.
+    pub fn tail(&self, bubble_w: i32, bubble_h: i32) -> (i32, i32) {
+        if self.mirrored { (0, bubble_h) } else { (bubble_w, bubble_h) }
+    }
+
+    /// Top left of the text area, relative to the bubble's top left corner.
+    /// The text area is mirrored along with the art.
+    pub fn text_origin(&self, bubble_w: i32) -> (i32, i32) {
+        let x = if self.mirrored {
+            bubble_w - utils::BUBBLE_TEXT_START_X - utils::BUBBLE_TEXT_WIDTH
+        } else {
+            utils::BUBBLE_TEXT_START_X
+        };
+        (x, utils::BUBBLE_TEXT_START_Y)
+    }
+}
+
+/// Places a `bubble_w` x `bubble_h` bubble next to a parrot whose top left corner is at
+/// `(parrot_x, parrot_y)`. Left is preferred, then right, then above; whatever side is
+/// picked, the bubble is pushed back inside the screen if it would stick out.
+pub fn place(
+    parrot_x: i32,
+    parrot_y: i32,
+    parrot_w: i32,
+    bubble_w: i32,
+    bubble_h: i32,
+    screen_w: i32,
+    screen_h: i32,
+) -> Placement {
+    let overlap = utils::BUBBLE_OFFSET_X;
+    let left_x = parrot_x + overlap - bubble_w;
+    let right_x = parrot_x + parrot_w - overlap;
+    let beside_y = parrot_y + utils::BUBBLE_OFFSET_Y;
+
+    let (side, x, y) = if left_x >= 0 {
+        (Side::Left, left_x, beside_y)
+    } else if right_x + bubble_w <= screen_w {
+        (Side::Right, right_x, beside_y)
+    } else {
+        // Neither side fits (parrot wider than the screen allows), go above it
+        let x = parrot_x + (parrot_w - bubble_w) / 2;
+        (Side::Above, x, parrot_y + overlap - bubble_h)
+    };
+
+    let x = x.min(screen_w - bubble_w).max(0);
+    let y = y.min(screen_h - bubble_h).max(0);
+
+    // Point the tail at whichever side of the bubble the parrot is on
+    let mirrored = match side {
+        Side::Left => false,
+        Side::Right => true,
+        Side::Above => parrot_x + parrot_w / 2 < x + bubble_w / 2,
+    };
+    Placement { x, y, mirrored }
+}
@@ -0,0 +1,72 @@
+use crate::deck::Card;
+use crate::review::Grade;
+use crate::utils;
+
+// Quiz mode: the bubble first asks with just the term, and the rest of the card is only
+// shown once the user answers. With multiple choice the prompt also lists a few meanings
+// (the right one and others taken from the deck) that can be clicked.
+
+/// A question about one card.
+pub struct Quiz {
+    pub card: usize,          // Index of the card being asked about
+    choices: Vec<usize>,      // Cards whose meanings are offered, empty without multiple choice
+    pub revealed: bool,       // The answer is showing
+    picked: Option<usize>,    // Position in `choices` the user clicked
+}
+
+impl Quiz {
+    /// Sets up a question for `cards[card]`. Multiple choice needs at least one other card
+    /// with a different meaning, otherwise the quiz is just prompt and reveal.
+    pub fn new(cards: &[Card], card: usize, rng: &mut utils::SimpleRng) -> Self {
+        let answer = &cards[card].meaning;
+        let mut choices = Vec::new();
+        if utils::QUIZ_CHOICES > 1 && !answer.is_empty() {
+            let mut others: Vec<usize> = (0..cards.len())
+                .filter(|&i| !cards[i].meaning.is_empty() && cards[i].meaning != *answer)
+                .collect();
+            rng.shuffle(&mut others);
+            // Different cards can share a meaning, only offer each one once
+            for index in others {
+                if choices.len() + 1 >= utils::QUIZ_CHOICES {
+                    break;
+                }
+                if !choices.iter().any(|&c: &usize| cards[c].meaning == cards[index].meaning) {
+                    choices.push(index);
+                }
+            }
+            if !choices.is_empty() {
+                choices.push(card);
+                rng.shuffle(&mut choices);
+            }
+        }
+        Quiz { card, choices, revealed: false, picked: None }
+    }
+
+    /// The question as paragraphs of markup, each with the choice it belongs to (if any).
+    pub fn prompt(&self, cards: &[Card]) -> Vec<(String, Option<usize>)> {
+        let mut paragraphs = vec![(format!("<s>{}</s>", cards[self.card].term), None)];
+        for (position, &choice) in self.choices.iter().enumerate() {
+            paragraphs.push((format!("{}. {}", position + 1, cards[choice].meaning), Some(position)));
+        }
+        paragraphs
+    }
+
+    /// Answers the question with the choice at `position` and reveals the card.
+    /// Returns `Grade::Good` if that was the right meaning, `Grade::Again` otherwise.
+    pub fn pick(&mut self, position: usize) -> Grade {
+        self.picked = Some(position);
+        self.revealed = true;
+        if self.choices.get(position) == Some(&self.card) { Grade::Good } else { Grade::Again }
+    }
+
+    /// The full card, after the question has been answered or given up on. A picked choice
+    /// is marked right or wrong above it.
+    pub fn answer(&self, cards: &[Card]) -> String {
+        let card = &cards[self.card];
+        match self.picked.map(|position| self.choices[position]) {
+            Some(choice) if choice == self.card => format!("<c=#080>✓</c> {}", card.message()),
+            Some(choice) => format!("<c=#c00>✗ {}</c><br>{}", cards[choice].meaning, card.message()),
+            None => card.message(),
+        }
+    }
+}
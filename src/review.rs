@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::deck::Card;
+use crate::utils;
+
+// Spaced repetition (SM-2). Every card the user has been asked about gets an ease factor
+// and an interval; knowing it pushes the next review further out, forgetting it brings
+// the card back soon and makes it come round more often. Review state is kept in a JSON
+// file keyed by card so it survives restarts and deck edits.
+
+/// How well the user remembered a card.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Grade {
+    Again, // Didn't know it
+    Good,  // Knew it
+}
+
+impl Grade {
+    // SM-2 rates answers from 0 to 5, 3 and up counts as remembered
+    fn quality(&self) -> f32 {
+        match self {
+            Grade::Again => 1.0,
+            Grade::Good => 4.0,
+        }
+    }
+}
+
+/// Review state of a single card.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReviewState {
+    pub ease: f32,
+    pub interval_days: f32,
+    pub repetitions: u32, // Correct answers in a row
+    pub lapses: u32,      // Times the card was forgotten
+    pub due: u64,         // Unix seconds
+    #[serde(default)]
+    pub reviews: u32,     // Times the card was answered
+    #[serde(default)]
+    pub correct: u32,     // Times it was answered right
+}
+
+impl Default for ReviewState {
+    fn default() -> Self {
+        ReviewState { ease: 2.5, interval_days: 0.0, repetitions: 0, lapses: 0, due: 0, reviews: 0, correct: 0 }
+    }
+}
+
+impl ReviewState {
+    /// Applies an answer given at `now` (unix seconds).
+    pub fn grade(&mut self, grade: Grade, now: u64) {
+        let q = grade.quality();
+        // SM-2 only adjusts the ease on a pass, a lapse just starts the card over
+        if q >= 3.0 {
+            self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(1.3);
+        }
+        self.reviews += 1;
+        match grade {
+            Grade::Again => {
+                self.repetitions = 0;
+                self.lapses += 1;
+                self.interval_days = 0.0;
+                // Ask again later in the session rather than tomorrow
+                self.due = now + utils::REVIEW_AGAIN_SECONDS;
+            }
+            Grade::Good => {
+                self.correct += 1;
+                self.repetitions += 1;
+                self.interval_days = match self.repetitions {
+                    1 => 1.0,
+                    2 => 6.0,
+                    _ => (self.interval_days * self.ease).round(),
+                };
+                self.due = now + (self.interval_days * 86400.0) as u64;
+            }
+        }
+    }
+}
+
+/// Review state for a whole deck, saved to `path`.
+pub struct Reviews {
+    path: String,
+    states: HashMap<String, ReviewState>,
+}
+
+impl Reviews {
+    /// Loads saved review state, starting fresh if the file is missing or unreadable.
+    pub fn load(path: &str) -> Self {
+        let states = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Failed to read review state from {}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Reviews { path: path.to_string(), states }
+    }
+
+    pub fn save(&self) {
+        match serde_json::to_string_pretty(&self.states) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    eprintln!("Failed to save review state to {}: {}", self.path, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to save review state: {}", e),
+        }
+    }
+
+    pub fn state(&self, card: &Card) -> Option<&ReviewState> {
+        self.states.get(&card.key())
+    }
+
+    /// Picks the card to show next: the most overdue card, then a card that hasn't been
+    /// seen yet, and if everything has been reviewed, the one that comes due soonest.
+    /// Only the `candidates` (indices into `cards`) are considered.
+    pub fn next_card(&self, cards: &[Card], candidates: &[usize], now: u64) -> Option<usize> {
+        let due = |index: &usize| self.state(&cards[*index]).map(|state| state.due);
+        let candidates = || candidates.iter().copied();
+        let overdue = candidates()
+            .filter(|index| due(index).is_some_and(|due| due <= now))
+            .min_by_key(|index| due(index));
+        let unseen = || candidates().find(|index| due(index).is_none());
+        let soonest = || candidates().min_by_key(|index| due(index));
+        overdue.or_else(unseen).or_else(soonest)
+    }
+
+    /// Records an answer for `card` and saves right away.
+    pub fn grade(&mut self, card: &Card, grade: Grade, now: u64) {
+        self.states.entry(card.key()).or_default().grade(grade, now);
+        self.save();
+    }
+}
+
+/// Current time in unix seconds.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_800_000_000;
+    const DAY: u64 = 86400;
+
+    // Grades a fresh card in turn, returning its state after each answer
+    fn answers(grades: &[Grade]) -> Vec<ReviewState> {
+        let mut state = ReviewState::default();
+        grades.iter()
+            .map(|grade| {
+                state.grade(*grade, NOW);
+                state.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn good_answers_space_the_card_out() {
+        let states = answers(&[Grade::Good; 4]);
+        let intervals: Vec<f32> = states.iter().map(|state| state.interval_days).collect();
+        // 1 day, 6 days, then the interval times the ease (2.5), rounded
+        assert_eq!(intervals, [1.0, 6.0, 15.0, 38.0]);
+        assert_eq!(states[3].repetitions, 4);
+        assert_eq!((states[3].reviews, states[3].correct), (4, 4));
+    }
+
+    #[test]
+    fn due_dates_follow_the_interval() {
+        let states = answers(&[Grade::Good, Grade::Good, Grade::Again]);
+        assert_eq!(states[0].due, NOW + DAY);
+        assert_eq!(states[1].due, NOW + 6 * DAY);
+        assert_eq!(states[2].due, NOW + utils::REVIEW_AGAIN_SECONDS);
+    }
+
+    #[test]
+    fn again_starts_the_card_over() {
+        let states = answers(&[Grade::Good, Grade::Good, Grade::Good, Grade::Again, Grade::Good]);
+        let lapsed = &states[3];
+        assert_eq!((lapsed.repetitions, lapsed.interval_days, lapsed.lapses), (0, 0.0, 1));
+        // A lapse leaves the ease as it was
+        assert_eq!(lapsed.ease, states[2].ease);
+        assert_eq!(states[4].interval_days, 1.0);
+        assert_eq!((states[4].reviews, states[4].correct), (5, 4));
+    }
+
+    #[test]
+    fn ease_never_drops_below_the_floor() {
+        let mut state = ReviewState { ease: 1.3, ..Default::default() };
+        for grade in [Grade::Again, Grade::Good, Grade::Again, Grade::Again, Grade::Good, Grade::Good] {
+            state.grade(grade, NOW);
+            assert!(state.ease >= 1.3, "ease fell to {}", state.ease);
+        }
+        assert!(answers(&[Grade::Again; 5]).iter().all(|state| state.ease == ReviewState::default().ease));
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike, Weekday};
+
+use crate::hours::{self, Window};
+
+// When a reminder fires. A line in reminders.txt can start with a schedule and a `|`:
+//
+//   every 45m | Drink some water            every 45 minutes (also "2h", "90")
+//   every 1h 09:00-18:00 weekdays | Stretch  only at these times and days (see hours.rs)
+//   at 09:55 weekdays | Stand-up in 5 min    at these times of day, "at 12:00,18:30" for several
+//   cron 0 */2 * * 1-5 | Look away          a cron expression: minute hour day month weekday
+//
+// Days are written as in hours.rs ("mon,wed,fri", "weekdays", "weekends"). Lines without
+// a schedule keep the old round robin every REMINDER_INTERVAL seconds. Times are local.
+
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Every { minutes: u32, window: Option<Window> },
+    At { times: Vec<NaiveTime>, days: Vec<Weekday> },
+    Cron(Cron),
+}
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let kind = words.next().unwrap_or("").to_ascii_lowercase();
+        let rest: Vec<&str> = words.collect();
+        match kind.as_str() {
+            "every" => {
+                let (&amount, mut rest) = rest.split_first().ok_or("\"every\" needs an interval like 45m")?;
+                let mut amount = amount.to_string();
+                // "every 45 minutes" as well as "every 45m"
+                if let Some((unit, after)) = rest.split_first()
+                    && UNITS.iter().any(|units| units.contains(&unit.to_ascii_lowercase().as_str()))
+                {
+                    amount.push_str(unit);
+                    rest = after;
+                }
+                let minutes = interval(&amount)?;
+                let hours = rest.first().filter(|word| word.contains(':'));
+                let days = days(&rest[hours.is_some() as usize..]);
+                let window = match hours {
+                    Some(hours) => Some(Window::parse(hours, &days)?),
+                    None if !days.is_empty() => Some(Window::all_day(&days)?),
+                    None => None,
+                };
+                Ok(Schedule::Every { minutes, window })
+            }
+            "at" => {
+                let (&times, rest) = rest.split_first().ok_or("\"at\" needs a time like 09:55")?;
+                let mut times = times.split(',').map(hours::time).collect::<Result<Vec<_>, _>>()?;
+                times.sort();
+                Ok(Schedule::At { times, days: hours::parse_days(&days(rest))? })
+            }
+            "cron" => Cron::parse(&rest).map(Schedule::Cron),
+            _ => Err(format!("unknown schedule \"{}\", use every, at or cron", spec.trim())),
+        }
+    }
+
+    /// The first time after `after` the reminder is due, `None` if it never is.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Schedule::Every { minutes, window } => {
+                let next = after + Duration::minutes(*minutes as i64);
+                let Some(window) = window else { return Some(next) };
+                // Wait for the window to open, a week covers every day it can be open on
+                let mut time = next.with_second(0)?.with_nanosecond(0)?;
+                for _ in 0..8 * 24 * 60 {
+                    if time >= next && window.contains(time) {
+                        return Some(time);
+                    }
+                    time += Duration::minutes(1);
+                }
+                None
+            }
+            Schedule::At { times, days } => {
+                (0..=7).flat_map(|offset| {
+                    let date = after.date() + Duration::days(offset);
+                    let on = days.is_empty() || days.contains(&date.weekday());
+                    times.iter().filter(move |_| on).map(move |time| date.and_time(*time))
+                })
+                .find(|time| *time > after)
+            }
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+/// A standard five field cron expression. Each field is `*`, a number, a range `a-b`,
+/// a step `*/n` or `a-b/n`, or a comma separated list of those. Weekdays run from 0
+/// (Sunday) to 7 (Sunday again) or are written as names. Like cron, when both the day
+/// of the month and the weekday are given, either one matching is enough.
+#[derive(Clone, Debug)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,     // Day of the month was `*`
+    any_weekday: bool, // Weekday was `*`
+}
+
+impl Cron {
+    fn parse(fields: &[&str]) -> Result<Self, String> {
+        let [minutes, hours, days, months, weekdays] = fields else {
+            return Err(format!("cron needs 5 fields (minute hour day month weekday), got {}", fields.len()));
+        };
+        let mut weekday_bits = field(&weekday_numbers(weekdays)?, 0, 7)?;
+        // 7 is Sunday too
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Ok(Cron {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: *days == "*",
+            any_weekday: *weekdays == "*",
+        })
+    }
+
+    fn day_matches(&self, time: NaiveDateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        let date = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        date && self.months & (1 << time.month()) != 0
+    }
+
+    fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Looking a few years ahead finds even "Feb 29 on a Monday"
+        let end = time + Duration::days(366 * 8);
+        while time < end {
+            if !self.day_matches(time) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) != 0 && self.minutes & (1 << time.minute()) != 0 {
+                return Some(time);
+            }
+            time += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+// One cron field as a bit set of the values it allows
+fn field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("invalid cron field \"{}\"", text);
+    let number = |text: &str| text.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(invalid);
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // "5/15" means every 15 from 5
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+// Weekday names in a cron field as numbers, "mon-fri" or "monday-friday" becomes "1-5"
+fn weekday_numbers(text: &str) -> Result<String, String> {
+    let mut numbers = String::new();
+    let mut name = String::new();
+    // A separator at the end ends the last name too
+    for c in text.chars().chain([',']) {
+        if c.is_ascii_alphabetic() {
+            name.push(c);
+            continue;
+        }
+        if !name.is_empty() {
+            let day: Weekday = name.parse().map_err(|_| format!("unknown day \"{}\" in cron field \"{}\"", name, text))?;
+            numbers.push_str(&day.num_days_from_sunday().to_string());
+            name.clear();
+        }
+        numbers.push(c);
+    }
+    numbers.pop();
+    Ok(numbers)
+}
+
+// Units an interval can be given in: minutes, then hours
+const UNITS: [&[&str]; 2] = [&["m", "min", "mins", "minute", "minutes"], &["h", "hour", "hours"]];
+
+/// Minutes in "45m", "45min", "2h" or a bare "45".
+pub fn interval(text: &str) -> Result<u32, String> {
+    let text = text.to_ascii_lowercase();
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let amount: u32 = digits.parse().map_err(|_| format!("invalid interval \"{}\"", text))?;
+    let unit = &text[digits.len()..];
+    let minutes = if unit.is_empty() || UNITS[0].contains(&unit) {
+        amount
+    } else if UNITS[1].contains(&unit) {
+        amount.checked_mul(60).ok_or_else(|| format!("interval \"{}\" is too long", text))?
+    } else {
+        return Err(format!("invalid interval \"{}\"", text));
+    };
+    if minutes == 0 {
+        return Err("the interval can't be zero".to_string());
+    }
+    Ok(minutes)
+}
+
+// Day words, which can be given separately or with commas ("mon wed" or "mon,wed")
+fn days(words: &[&str]) -> Vec<String> {
+    words.iter().flat_map(|word| word.split(',')).filter(|day| !day.is_empty()).map(|day| day.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    // Monday 2026-10-19 at `hour`:`minute`
+    fn monday(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn next(spec: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        Schedule::parse(spec).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn intervals_take_minutes_and_hours() {
+        assert_eq!(interval("45"), Ok(45));
+        assert_eq!(interval("45m"), Ok(45));
+        assert_eq!(interval("2h"), Ok(120));
+        assert!(interval("0m").is_err());
+        assert!(interval("5d").is_err());
+        assert!(interval("99999999h").is_err());
+        assert_eq!(next("every 45 minutes", monday(9, 0)), Some(monday(9, 45)));
+        assert_eq!(next("every 2 hours", monday(9, 0)), Some(monday(11, 0)));
+    }
+
+    #[test]
+    fn every_waits_for_its_window() {
+        let spec = "every 1h 09:00-18:00 weekdays";
+        assert_eq!(next(spec, monday(9, 30)), Some(monday(10, 30)));
+        // Friday evening goes on to Monday morning
+        let friday = monday(17, 30) + Duration::days(4);
+        assert_eq!(next(spec, friday), Some(monday(9, 0) + Duration::days(7)));
+    }
+
+    #[test]
+    fn at_fires_at_the_given_times_and_days() {
+        assert_eq!(next("at 12:00,09:30", monday(10, 0)), Some(monday(12, 0)));
+        assert_eq!(next("at 12:00,09:30", monday(12, 0)), Some(monday(9, 30) + Duration::days(1)));
+        assert_eq!(next("at 09:55 weekends", monday(8, 0)), Some(monday(9, 55) + Duration::days(5)));
+        assert!(Schedule::parse("at 25:00").is_err());
+        assert!(Schedule::parse("at 09:00 someday").is_err());
+    }
+
+    #[test]
+    fn cron_follows_its_fields() {
+        assert_eq!(next("cron */20 9-10 * * 1-5", monday(10, 50)), Some(monday(9, 0) + Duration::days(1)));
+        assert_eq!(next("cron 5/15 * * * *", monday(9, 21)), Some(monday(9, 35)));
+        // Either the day of the month or the weekday is enough
+        assert_eq!(next("cron 0 0 1 * fri", monday(0, 0)), Some(monday(0, 0) + Duration::days(4)));
+        assert!(Schedule::parse("cron 0 0 * *").is_err());
+        assert!(Schedule::parse("cron 60 * * * *").is_err());
+        assert!(Schedule::parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn cron_weekdays_can_be_names() {
+        let sunday = Some(monday(0, 0) + Duration::days(6));
+        assert_eq!(next("cron 0 0 * * sunday", monday(0, 0)), sunday);
+        assert_eq!(next("cron 0 0 * * Sun", monday(0, 0)), sunday);
+        assert_eq!(next("cron 0 0 * * 7", monday(0, 0)), sunday);
+        assert_eq!(next("cron 0 12 * * monday-wednesday", monday(13, 0)), Some(monday(12, 0) + Duration::days(1)));
+        assert_eq!(next("cron 0 12 * * sat,mon", monday(13, 0)), Some(monday(12, 0) + Duration::days(5)));
+        assert!(Schedule::parse("cron 0 0 * * someday").is_err());
+    }
+}
@@ -1,53 +1,864 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::fs;
-use std::sync::Mutex;
-use std::time::{Duration, Instant};
-use once_cell::sync::Lazy;
 
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::hours::Window;
+use crate::schedule::{self, Schedule};
 use crate::utils;
 
-static REMINDERS: Lazy<Vec<String>> = Lazy::new(|| {
-    fs::read_to_string("reminders.txt")
-        .map(|content| content.lines().filter(|l| !l.trim().is_empty()).map(|l| l.to_string()).collect())
-        .unwrap_or_else(|_| vec![])
-});
+// Reminders from reminders.txt. Lines starting with a schedule (see schedule.rs) fire at
+// their own times, the rest are shown round robin every REMINDER_INTERVAL seconds.
+// The scheduler reads the time from a `Clock` and its reminders from a `ReminderSource`,
+// so tests can run it on fake time and several schedules can run side by side.
+//
+// With `persist`, the round robin position, when each reminder is next due and the
+// reminders still waiting are saved, so a restart carries on where it left off. Reminders that came due while the parrot
+// wasn't running, or while the machine was asleep (ticks far apart), are handled by the
+// `Missed` policy.
+//
+// Reminders that come due wait in a queue until the user right-clicks, the most pressing
+// first (see `Priority`). The same text coming due again while it waits is counted
+// instead of queued twice, and reminders left unseen for REMINDER_EXPIRY_MINUTES (or
+// their own "expires" time) are dropped.
+//
+// A reminder taken for the bubble stays there until the user says what to do with it:
+// done (acknowledged), snooze (queued again REMINDER_SNOOZE_MINUTES later) or nothing
+// (dismissed when the bubble closes). How often each happened is kept with the saved
+// state, per reminder text.
+//
+// During quiet hours only urgent reminders are shown, the rest are held back until the
+// quiet hours end or dropped, as the `Quiet` policy says.
 
-static STATE: Lazy<Mutex<SchedulerState>> = Lazy::new(|| Mutex::new(SchedulerState::new()));
+/// Where the scheduler gets the current local time from.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
 
-struct SchedulerState {
-    last_reminder_time: Instant,
-    pending_message: Option<String>,
-    reminder_index: usize,
+/// The real wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
 }
 
-impl SchedulerState {
-    fn new() -> Self {
-        SchedulerState {
-            last_reminder_time: Instant::now(),
-            pending_message: None,
-            reminder_index: 0,
+/// Where the scheduler gets its reminders from.
+pub trait ReminderSource {
+    fn reminders(&self) -> Vec<Reminder>;
+}
+
+/// Reminders read from a file, one per line (see schedule.rs for the format).
+pub struct ReminderFile(pub String);
+
+impl ReminderSource for ReminderFile {
+    fn reminders(&self) -> Vec<Reminder> {
+        fs::read_to_string(&self.0)
+            .map(|content| content.lines().filter(|l| !l.trim().is_empty()).map(Reminder::parse).collect())
+            .unwrap_or_else(|_| vec![])
+    }
+}
+
+impl ReminderSource for Vec<Reminder> {
+    fn reminders(&self) -> Vec<Reminder> {
+        self.clone()
+    }
+}
+
+/// How pressing a reminder is. Pending reminders are shown highest priority first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            "urgent" => Some(Priority::Urgent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Reminder {
+    pub text: String,
+    pub schedule: Option<Schedule>, // None for the round robin
+    pub priority: Priority,
+    pub expires: Option<u32>,       // Minutes it waits to be seen, REMINDER_EXPIRY_MINUTES if not given
+    key: String,                    // The whole line, identifies the reminder in saved state
+}
+
+impl Reminder {
+    /// A line is "schedule | text" when the part before the first `|` is a valid schedule.
+    /// The schedule can be preceded or followed by a priority (low, high, urgent) and
+    /// "expires 10m", and can be left out to give a round robin reminder just those.
+    pub fn parse(line: &str) -> Self {
+        let key = line.trim().to_string();
+        if let Some((spec, text)) = line.split_once('|') {
+            let mut priority = None;
+            let mut expires = None;
+            let mut words = Vec::new();
+            let mut iter = spec.split_whitespace();
+            while let Some(word) = iter.next() {
+                if let Some(level) = Priority::from_name(word) {
+                    priority = Some(level);
+                } else if word.eq_ignore_ascii_case("expires") {
+                    match iter.next().map(schedule::interval) {
+                        Some(Ok(minutes)) => expires = Some(minutes),
+                        Some(Err(e)) => eprintln!("Invalid expiry in \"{}\": {}", line, e),
+                        None => eprintln!("Invalid expiry in \"{}\": \"expires\" needs a time like 10m", line),
+                    }
+                } else {
+                    words.push(word);
+                }
+            }
+            let text = text.trim().to_string();
+            let priority_given = priority.is_some() || expires.is_some();
+            let priority = priority.unwrap_or_default();
+            if words.is_empty() && priority_given {
+                return Reminder { text, schedule: None, priority, expires, key };
+            }
+            match Schedule::parse(&words.join(" ")) {
+                Ok(schedule) => return Reminder { text, schedule: Some(schedule), priority, expires, key },
+                // Only complain when it was clearly meant as a schedule
+                Err(e) if words.first().is_some_and(|kind| ["every", "at", "cron"].contains(&kind.to_ascii_lowercase().as_str())) => {
+                    eprintln!("Invalid schedule, showing \"{}\" round robin: {}", line, e);
+                }
+                Err(_) => {}
+            }
         }
+        Reminder { text: line.to_string(), schedule: None, priority: Priority::Normal, expires: None, key }
     }
 }
 
-pub fn tick() {
-    let mut state = STATE.lock().unwrap();
-    if state.pending_message.is_none() && !REMINDERS.is_empty() {
-        if state.last_reminder_time.elapsed() >= Duration::from_secs( utils::REMINDER_INTERVAL) {
-            // Pick next reminder (round robin)
-            let msg = REMINDERS[state.reminder_index % REMINDERS.len()].clone();
-            state.pending_message = Some(msg);
-            state.reminder_index = (state.reminder_index + 1) % REMINDERS.len();
-            state.last_reminder_time = Instant::now();
+/// What happens to reminders that came due while the parrot wasn't running or the
+/// machine was asleep.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)] // Chosen in utils.rs
+pub enum Missed {
+    FireOnce, // Each missed reminder is shown once, however many times it came due
+    FireAll,  // Every missed occurrence counts, up to MISSED_REMINDER_LIMIT per reminder
+    Skip,     // Missed reminders are dropped and wait for their next time
+}
+
+/// What happens to reminders during quiet hours. Urgent ones are shown either way.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)] // Chosen in utils.rs
+pub enum Quiet {
+    Defer,    // They wait until the quiet hours are over
+    Suppress, // They are dropped
+}
+
+/// What the user can do with the reminder in the bubble.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Snooze,
+    Done,
+}
+
+impl Action {
+    pub const ALL: [Action; 2] = [Action::Snooze, Action::Done];
+
+    fn label(&self) -> String {
+        match self {
+            Action::Snooze => format!("<c=#888>⏰ Snooze {} min</c>", utils::REMINDER_SNOOZE_MINUTES),
+            Action::Done => "<c=#080>✓ Done</c>".to_string(),
         }
     }
 }
 
-pub fn has_message_ready() -> bool {
-    let state = STATE.lock().unwrap();
-    state.pending_message.is_some()
+/// The reminder as paragraphs of markup for the bubble, the actions tagged with their
+/// position in `Action::ALL`.
+pub fn prompt(message: &str) -> Vec<(String, Option<usize>)> {
+    let mut paragraphs = vec![(message.to_string(), None)];
+    paragraphs.extend(Action::ALL.iter().enumerate().map(|(position, action)| (action.label(), Some(position))));
+    paragraphs
+}
+
+// A reminder waiting for the user to right-click
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Pending {
+    text: String,
+    priority: Priority,
+    queued: NaiveDateTime,
+    expires: Option<NaiveDateTime>,
+    count: u32, // Times it came due while waiting, duplicates are shown once
+    #[serde(default)]
+    snoozed_until: Option<NaiveDateTime>, // Snoozed or deferred, not shown before then
+}
+
+impl Pending {
+    fn message(&self) -> String {
+        match self.count {
+            0 | 1 => self.text.clone(),
+            count => format!("{} <c=#888>×{}</c>", self.text, count),
+        }
+    }
+
+    fn ready(&self, now: NaiveDateTime) -> bool {
+        self.snoozed_until.is_none_or(|until| until <= now)
+    }
+
+    // Holds it back until `until`, behind what is already waiting by then. It waits to be
+    // seen as long again once it is back.
+    fn defer(&mut self, until: NaiveDateTime) {
+        if let Some(expires) = self.expires {
+            self.expires = Some(until + (expires - self.queued));
+        }
+        self.queued = until;
+        self.snoozed_until = Some(until);
+    }
+}
+
+// What the user did with a reminder, by its text
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Acknowledgements {
+    acknowledged: u32,
+    snoozed: u32,
+    dismissed: u32,
+    last_acknowledged: Option<NaiveDateTime>,
+}
+
+// What is saved between runs
+#[derive(Default, Serialize, Deserialize)]
+struct SavedState {
+    last_tick: Option<NaiveDateTime>,
+    last_reminder_time: Option<NaiveDateTime>,
+    reminder_index: usize,
+    due: HashMap<String, NaiveDateTime>, // By reminder key
+    #[serde(default)]
+    pending: Vec<Pending>,
+    #[serde(default)]
+    acknowledgements: HashMap<String, Acknowledgements>,
 }
 
-pub fn get_message() -> Option<String> {
-    let mut state = STATE.lock().unwrap();
-    state.pending_message.take()
-} 
\ No newline at end of file
+pub struct Scheduler {
+    clock: Box<dyn Clock>,
+    reminders: Vec<Reminder>,
+    missed: Missed,
+    path: Option<String>,              // Where state is saved, if anywhere
+    last_tick: NaiveDateTime,
+    last_saved: NaiveDateTime,
+    last_reminder_time: NaiveDateTime, // When the round robin last fired
+    pending: Vec<Pending>,             // In the order they were queued
+    showing: Option<Pending>,          // Taken for the bubble, waiting for an answer
+    quiet: Option<(Window, Quiet)>,    // Quiet hours, if any
+    acknowledgements: HashMap<String, Acknowledgements>,
+    reminder_index: usize,
+    due: Vec<Option<NaiveDateTime>>, // Next time each scheduled reminder fires, by position in `reminders`
+}
+
+impl Scheduler {
+    pub fn new(clock: Box<dyn Clock>, source: &dyn ReminderSource, missed: Missed) -> Self {
+        let now = clock.now();
+        let reminders = source.reminders();
+        let due = reminders.iter()
+            .map(|reminder| reminder.schedule.as_ref().and_then(|schedule| schedule.next_after(now)))
+            .collect();
+        Scheduler {
+            clock,
+            reminders,
+            missed,
+            path: None,
+            last_tick: now,
+            last_saved: now,
+            last_reminder_time: now,
+            pending: Vec::new(),
+            showing: None,
+            quiet: None,
+            acknowledgements: HashMap::new(),
+            reminder_index: 0,
+            due,
+        }
+    }
+
+    /// Restores the state saved at `path`, catches up on what was missed since, and
+    /// saves there from now on.
+    pub fn persist(&mut self, path: &str) {
+        self.path = Some(path.to_string());
+        let saved: SavedState = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Failed to read scheduler state from {}: {}", path, e);
+                SavedState::default()
+            }),
+            Err(_) => SavedState::default(),
+        };
+        let now = self.clock.now();
+        self.reminder_index = saved.reminder_index;
+        if let Some(time) = saved.last_reminder_time.filter(|time| *time <= now) {
+            self.last_reminder_time = time;
+        }
+        for (index, reminder) in self.reminders.iter().enumerate() {
+            if let Some(due) = saved.due.get(&reminder.key) {
+                self.due[index] = Some(*due);
+            }
+        }
+        self.pending = saved.pending;
+        self.acknowledgements = saved.acknowledgements;
+        if saved.last_tick.is_some_and(|time| time < now) {
+            self.catch_up(now);
+        }
+        self.last_tick = now;
+        self.save();
+    }
+
+    /// Holds back or drops all but urgent reminders during `window`.
+    pub fn quiet_hours(&mut self, window: Window, quiet: Quiet) {
+        self.quiet = Some((window, quiet));
+    }
+
+    /// Whether it is quiet hours right now.
+    pub fn is_quiet(&self) -> bool {
+        self.quiet.as_ref().is_some_and(|(window, _)| window.contains(self.clock.now()))
+    }
+
+    fn save(&mut self) {
+        let Some(path) = &self.path else { return };
+        let state = SavedState {
+            last_tick: Some(self.last_tick),
+            last_reminder_time: Some(self.last_reminder_time),
+            reminder_index: self.reminder_index,
+            due: self.reminders.iter().zip(&self.due)
+                .filter_map(|(reminder, due)| due.map(|due| (reminder.key.clone(), due)))
+                .collect(),
+            // The one in the bubble is still waiting for an answer, keep it if we quit now
+            pending: self.showing.iter().chain(&self.pending).cloned().collect(),
+            acknowledgements: self.acknowledgements.clone(),
+        };
+        match serde_json::to_string_pretty(&state) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    eprintln!("Failed to save scheduler state to {}: {}", path, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to save scheduler state: {}", e),
+        }
+        self.last_saved = self.last_tick;
+    }
+
+    // Adds the reminder at `index` to the pending ones, or counts it again if the same
+    // text is already waiting
+    fn enqueue(&mut self, index: usize, now: NaiveDateTime) {
+        let reminder = &self.reminders[index];
+        let minutes = reminder.expires.unwrap_or(utils::REMINDER_EXPIRY_MINUTES);
+        let expiry = (minutes > 0).then(|| Duration::minutes(minutes as i64));
+        match self.pending.iter_mut().find(|pending| pending.text == reminder.text) {
+            Some(pending) => {
+                pending.count += 1;
+                pending.priority = pending.priority.max(reminder.priority);
+                // A snoozed or deferred one still gets its full time once it is back
+                let from = pending.snoozed_until.map_or(now, |until| until.max(now));
+                pending.expires = expiry.map(|expiry| from + expiry);
+            }
+            None => {
+                let expires = expiry.map(|expiry| now + expiry);
+                self.pending.push(Pending { text: reminder.text.clone(), priority: reminder.priority, queued: now, expires, count: 1, snoozed_until: None });
+            }
+        }
+    }
+
+    // Positions of the round robin reminders in `reminders`
+    fn round_robin(&self) -> Vec<usize> {
+        (0..self.reminders.len()).filter(|index| self.reminders[*index].schedule.is_none()).collect()
+    }
+
+    // Queues the next round robin reminder
+    fn enqueue_round_robin(&mut self, round_robin: &[usize], now: NaiveDateTime) {
+        self.enqueue(round_robin[self.reminder_index % round_robin.len()], now);
+        self.reminder_index = (self.reminder_index + 1) % round_robin.len();
+    }
+
+    // Applies the Missed policy to everything that came due while nothing was ticking
+    fn catch_up(&mut self, now: NaiveDateTime) {
+        let interval = Duration::seconds(utils::REMINDER_INTERVAL as i64);
+        for index in 0..self.reminders.len() {
+            let Some(schedule) = &self.reminders[index].schedule else { continue };
+            let Some(due) = self.due[index].filter(|due| *due <= now) else { continue };
+            match self.missed {
+                Missed::FireOnce => {}
+                Missed::Skip => self.due[index] = schedule.next_after(now),
+                Missed::FireAll => {
+                    // Count the missed occurrences and carry on in step with them
+                    let mut missed = 1;
+                    let mut next = schedule.next_after(due);
+                    while let Some(time) = next.filter(|time| *time <= now) {
+                        missed += 1;
+                        next = schedule.next_after(time);
+                    }
+                    self.due[index] = next;
+                    for _ in 0..missed.min(utils::MISSED_REMINDER_LIMIT) {
+                        self.enqueue(index, now);
+                    }
+                }
+            }
+        }
+        let round_robin = self.round_robin();
+        if !round_robin.is_empty() && now - self.last_reminder_time >= interval {
+            match self.missed {
+                Missed::FireOnce => {}
+                Missed::Skip => self.last_reminder_time = now,
+                Missed::FireAll => {
+                    let missed = ((now - self.last_reminder_time).num_seconds() / interval.num_seconds()) as usize;
+                    for _ in 0..missed.min(utils::MISSED_REMINDER_LIMIT) {
+                        self.enqueue_round_robin(&round_robin, now);
+                    }
+                    self.last_reminder_time = now;
+                }
+            }
+        }
+    }
+
+    /// Queues every reminder that has come due and drops those left unseen too long.
+    pub fn tick(&mut self) {
+        let now = self.clock.now();
+        if now - self.last_tick > Duration::seconds(utils::SCHEDULER_SLEEP_SECONDS as i64) {
+            // The machine was asleep (or the clock jumped)
+            self.catch_up(now);
+        }
+        self.last_tick = now;
+        let waiting = self.pending.len();
+        self.pending.retain(|pending| pending.expires.is_none_or(|expires| expires > now));
+        let mut changed = self.pending.len() != waiting;
+
+        // Scheduled reminders in the order they came due
+        let mut due: Vec<usize> = (0..self.reminders.len())
+            .filter(|index| self.due[*index].is_some_and(|due| due <= now))
+            .collect();
+        due.sort_by_key(|index| self.due[*index]);
+        for index in due {
+            self.enqueue(index, now);
+            self.due[index] = self.reminders[index].schedule.as_ref().and_then(|schedule| schedule.next_after(now));
+            changed = true;
+        }
+
+        let round_robin = self.round_robin();
+        let interval = Duration::seconds(utils::REMINDER_INTERVAL as i64);
+        if !round_robin.is_empty() && now - self.last_reminder_time >= interval {
+            self.enqueue_round_robin(&round_robin, now);
+            self.last_reminder_time = now;
+            changed = true;
+        }
+
+        if let Some((window, quiet)) = &self.quiet
+            && window.contains(now)
+        {
+            let held = |pending: &Pending| pending.priority < Priority::Urgent && pending.ready(now);
+            match quiet {
+                Quiet::Suppress => {
+                    let waiting = self.pending.len();
+                    self.pending.retain(|pending| !held(pending));
+                    changed |= self.pending.len() != waiting;
+                }
+                Quiet::Defer => {
+                    if let Some(until) = window.end_after(now) {
+                        for pending in self.pending.iter_mut().filter(|pending| held(pending)) {
+                            pending.defer(until);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if changed || now - self.last_saved >= Duration::minutes(1) {
+            self.save();
+        }
+    }
+
+    pub fn has_message_ready(&self) -> bool {
+        self.pending_count() > 0
+    }
+
+    /// How many reminders are waiting, for the counter on the parrot. Snoozed ones don't
+    /// count until they are back.
+    pub fn pending_count(&self) -> usize {
+        let now = self.clock.now();
+        self.pending.iter().filter(|pending| pending.ready(now)).count()
+    }
+
+    /// Takes the most pressing reminder, the oldest first among equals, for the bubble.
+    /// It stays there until it is acknowledged, snoozed or dismissed, and taking the next
+    /// one dismisses it.
+    pub fn get_message(&mut self) -> Option<String> {
+        let now = self.clock.now();
+        let index = (0..self.pending.len())
+            .filter(|index| self.pending[*index].ready(now))
+            .min_by_key(|index| (Reverse(self.pending[*index].priority), self.pending[*index].queued))?;
+        self.dismiss();
+        let pending = self.pending.remove(index);
+        let message = pending.message();
+        self.showing = Some(pending);
+        self.save();
+        Some(message)
+    }
+
+    /// Whether a reminder is in the bubble, waiting for an answer.
+    pub fn is_showing(&self) -> bool {
+        self.showing.is_some()
+    }
+
+    /// Does `action` with the reminder in the bubble.
+    pub fn answer(&mut self, action: Action) {
+        match action {
+            Action::Snooze => self.snooze(),
+            Action::Done => self.acknowledge(),
+        }
+    }
+
+    /// The user has done what the reminder in the bubble asked.
+    pub fn acknowledge(&mut self) {
+        let Some(pending) = self.showing.take() else { return };
+        let now = self.clock.now();
+        let acknowledgements = self.acknowledgements.entry(pending.text).or_default();
+        acknowledgements.acknowledged += 1;
+        acknowledgements.last_acknowledged = Some(now);
+        self.save();
+    }
+
+    /// Puts the reminder in the bubble back in the queue for REMINDER_SNOOZE_MINUTES.
+    pub fn snooze(&mut self) {
+        let Some(mut pending) = self.showing.take() else { return };
+        let until = self.clock.now() + Duration::minutes(utils::REMINDER_SNOOZE_MINUTES as i64);
+        self.acknowledgements.entry(pending.text.clone()).or_default().snoozed += 1;
+        pending.defer(until);
+        match self.pending.iter_mut().find(|waiting| waiting.text == pending.text) {
+            // Came due again in the meantime
+            Some(waiting) => waiting.count += pending.count,
+            None => self.pending.push(pending),
+        }
+        self.save();
+    }
+
+    /// The bubble with the reminder closed without an answer.
+    pub fn dismiss(&mut self) {
+        let Some(pending) = self.showing.take() else { return };
+        self.acknowledgements.entry(pending.text).or_default().dismissed += 1;
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    // A clock the test moves forward by hand
+    #[derive(Clone)]
+    struct FakeClock(Rc<Cell<NaiveDateTime>>);
+
+    impl FakeClock {
+        fn at(hour: u32, minute: u32) -> Self {
+            // A Monday
+            FakeClock(Rc::new(Cell::new(NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(hour, minute, 0).unwrap())))
+        }
+
+        fn advance(&self, minutes: i64) {
+            self.0.set(self.0.get() + Duration::minutes(minutes));
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0.get()
+        }
+    }
+
+    fn scheduler(clock: &FakeClock, lines: &[&str]) -> Scheduler {
+        scheduler_missing(clock, lines, Missed::FireOnce)
+    }
+
+    fn scheduler_missing(clock: &FakeClock, lines: &[&str], missed: Missed) -> Scheduler {
+        let reminders: Vec<Reminder> = lines.iter().map(|line| Reminder::parse(line)).collect();
+        Scheduler::new(Box::new(clock.clone()), &reminders, missed)
+    }
+
+    // A fresh state file for one test
+    fn state_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("parrot-scheduler-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn times(fired: &[(NaiveDateTime, String)]) -> Vec<String> {
+        fired.iter().map(|(time, _)| time.format("%H:%M").to_string()).collect()
+    }
+
+    // Ticks once a minute for `minutes`, taking every reminder as soon as it is queued
+    fn run(scheduler: &mut Scheduler, clock: &FakeClock, minutes: i64) -> Vec<(NaiveDateTime, String)> {
+        let mut fired = Vec::new();
+        for _ in 0..minutes {
+            clock.advance(1);
+            scheduler.tick();
+            if let Some(message) = scheduler.get_message() {
+                fired.push((clock.now(), message));
+            }
+        }
+        fired
+    }
+
+    fn texts(fired: &[(NaiveDateTime, String)]) -> Vec<&str> {
+        fired.iter().map(|(_, text)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn nothing_fires_before_it_is_due() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["every 45m | Water"]);
+        assert!(run(&mut scheduler, &clock, 44).is_empty());
+        assert_eq!(texts(&run(&mut scheduler, &clock, 1)), ["Water"]);
+    }
+
+    #[test]
+    fn interval_reminders_repeat() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["every 45m | Water"]);
+        let fired = run(&mut scheduler, &clock, 180);
+        assert_eq!(times(&fired), ["09:45", "10:30", "11:15", "12:00"]);
+    }
+
+    #[test]
+    fn scheduled_reminders_fire_in_time_order() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["at 10:00 | Second", "at 09:55 weekdays | First", "at 11:00 weekends | Never"]);
+        assert_eq!(texts(&run(&mut scheduler, &clock, 120)), ["First", "Second"]);
+    }
+
+    #[test]
+    fn reminders_due_together_queue_in_time_order() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["at 09:30 | Later", "at 09:10 | Sooner"]);
+        // Nobody takes the first one until both are due
+        clock.advance(40);
+        scheduler.tick();
+        assert_eq!(scheduler.pending_count(), 2);
+        assert_eq!(scheduler.get_message().as_deref(), Some("Sooner"));
+        assert_eq!(scheduler.get_message().as_deref(), Some("Later"));
+        assert!(!scheduler.has_message_ready());
+    }
+
+    #[test]
+    fn pressing_reminders_go_first() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["at 09:10 | Normal", "at 09:20 low | Low", "urgent at 09:30 | Urgent", "high | Round robin"]);
+        clock.advance(utils::REMINDER_INTERVAL as i64 / 60);
+        scheduler.tick();
+        let taken: Vec<String> = std::iter::from_fn(|| scheduler.get_message()).collect();
+        assert_eq!(taken, ["Urgent", "Round robin", "Normal", "Low"]);
+    }
+
+    #[test]
+    fn waiting_duplicates_are_counted_once() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["every 10m | Water", "every 25m | Water", "every 15m | Stretch"]);
+        for _ in 0..30 {
+            clock.advance(1);
+            scheduler.tick();
+        }
+        assert_eq!(scheduler.pending_count(), 2);
+        assert_eq!(scheduler.get_message().as_deref(), Some("Water <c=#888>×4</c>"));
+        assert_eq!(scheduler.get_message().as_deref(), Some("Stretch <c=#888>×2</c>"));
+    }
+
+    #[test]
+    fn unseen_reminders_expire() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["at 09:10 expires 15m | Soon stale", "at 09:10 | Keeps"]);
+        clock.advance(10);
+        scheduler.tick();
+        assert_eq!(scheduler.pending_count(), 2);
+        clock.advance(15);
+        scheduler.tick();
+        assert_eq!(scheduler.get_message().as_deref(), Some("Keeps"));
+        assert!(!scheduler.has_message_ready());
+    }
+
+    #[test]
+    fn snoozed_reminders_come_back() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["at 09:10 | Stand up", "at 09:15 | Water"]);
+        clock.advance(10);
+        scheduler.tick();
+        assert_eq!(scheduler.get_message().as_deref(), Some("Stand up"));
+        scheduler.snooze();
+        assert!(!scheduler.is_showing());
+        assert!(!scheduler.has_message_ready());
+        let snooze = utils::REMINDER_SNOOZE_MINUTES as i64;
+        let fired = run(&mut scheduler, &clock, snooze);
+        assert_eq!(texts(&fired), ["Water", "Stand up"]);
+        assert_eq!(fired[1].0, FakeClock::at(9, 10).now() + Duration::minutes(snooze));
+    }
+
+    #[test]
+    fn snoozed_reminders_outlast_their_expiry() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["every 7m expires 2m | Water"]);
+        assert_eq!(times(&run(&mut scheduler, &clock, 7)), ["09:07"]);
+        scheduler.snooze();
+        // Due again at 09:14 while snoozed, it comes back with the snooze rather than expiring
+        let fired = run(&mut scheduler, &clock, utils::REMINDER_SNOOZE_MINUTES as i64);
+        assert_eq!(texts(&fired), ["Water <c=#888>×2</c>"]);
+        assert_eq!(fired[0].0, FakeClock::at(9, 7).now() + Duration::minutes(utils::REMINDER_SNOOZE_MINUTES as i64));
+    }
+
+    #[test]
+    fn answers_are_kept_per_reminder() {
+        let path = state_file("answers");
+        let clock = FakeClock::at(9, 0);
+        let mut first = scheduler(&clock, &["every 10m | Water"]);
+        first.persist(&path);
+        for action in [Action::Done, Action::Snooze, Action::Done] {
+            assert_eq!(run(&mut first, &clock, 10).len(), 1);
+            first.answer(action);
+        }
+        // Taking the next one, or closing the bubble, dismisses the one showing
+        run(&mut first, &clock, 20);
+        first.dismiss();
+        drop(first);
+
+        let mut second = scheduler(&clock, &["every 10m | Water"]);
+        second.persist(&path);
+        let water = &second.acknowledgements["Water"];
+        assert_eq!((water.acknowledged, water.snoozed, water.dismissed), (2, 1, 2));
+        assert_eq!(water.last_acknowledged, Some(FakeClock::at(9, 30).now()));
+        let _ = fs::remove_file(&path);
+    }
+
+    // "at 22:30 | Stretch" and an urgent "at 23:00 | Pills" from 21:50 to 07:50 with quiet nights
+    fn quiet_night(quiet: Quiet) -> Vec<(NaiveDateTime, String)> {
+        let clock = FakeClock::at(21, 50);
+        let mut scheduler = scheduler(&clock, &["at 22:30 | Stretch", "at 23:00 urgent | Pills"]);
+        scheduler.quiet_hours(Window::parse("22:00-07:00", &[]).unwrap(), quiet);
+        assert!(!scheduler.is_quiet());
+        let fired = run(&mut scheduler, &clock, 10);
+        assert!(scheduler.is_quiet());
+        fired.into_iter().chain(run(&mut scheduler, &clock, 10 * 60 - 10)).collect()
+    }
+
+    #[test]
+    fn quiet_hours_hold_reminders_until_morning() {
+        let fired = quiet_night(Quiet::Defer);
+        assert_eq!(texts(&fired), ["Pills", "Stretch"]);
+        assert_eq!(times(&fired), ["23:00", "07:00"]);
+    }
+
+    #[test]
+    fn quiet_hours_can_drop_reminders() {
+        let fired = quiet_night(Quiet::Suppress);
+        assert_eq!(texts(&fired), ["Pills"]);
+    }
+
+    #[test]
+    fn round_robin_uses_the_interval() {
+        let clock = FakeClock::at(9, 0);
+        let mut scheduler = scheduler(&clock, &["One", "Two"]);
+        let interval = utils::REMINDER_INTERVAL as i64 / 60;
+        let fired = run(&mut scheduler, &clock, interval * 3);
+        assert_eq!(texts(&fired), ["One", "Two", "One"]);
+        assert_eq!(fired[0].0, FakeClock::at(9, 0).now() + Duration::minutes(interval));
+    }
+
+    #[test]
+    fn cron_reminders_fire_on_matching_minutes() {
+        let clock = FakeClock::at(8, 50);
+        let mut scheduler = scheduler(&clock, &["cron */20 9-10 * * mon-fri | Look away"]);
+        let fired = run(&mut scheduler, &clock, 150);
+        assert_eq!(times(&fired), ["09:00", "09:20", "09:40", "10:00", "10:20", "10:40"]);
+    }
+
+    #[test]
+    fn round_robin_carries_on_after_a_restart() {
+        let path = state_file("round-robin");
+        let clock = FakeClock::at(9, 0);
+        let interval = utils::REMINDER_INTERVAL as i64 / 60;
+        let mut first = scheduler(&clock, &["One", "Two"]);
+        first.persist(&path);
+        assert_eq!(texts(&run(&mut first, &clock, interval)), ["One"]);
+        first.acknowledge();
+        drop(first);
+
+        let mut second = scheduler(&clock, &["One", "Two"]);
+        second.persist(&path);
+        assert_eq!(texts(&run(&mut second, &clock, interval)), ["Two"]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn the_reminder_showing_survives_a_restart() {
+        let path = state_file("showing");
+        let clock = FakeClock::at(9, 0);
+        let mut first = scheduler(&clock, &["at 09:10 | Stand up"]);
+        first.persist(&path);
+        assert_eq!(texts(&run(&mut first, &clock, 10)), ["Stand up"]);
+        drop(first);
+
+        let mut second = scheduler(&clock, &["at 09:10 | Stand up"]);
+        second.persist(&path);
+        assert_eq!(second.get_message().as_deref(), Some("Stand up"));
+        let _ = fs::remove_file(&path);
+    }
+
+    // Runs "every 45m" from 09:00 to 10:00, restarts at 13:59 and collects what fires until 15:00
+    fn restart_after_missing(name: &str, missed: Missed) -> Vec<(NaiveDateTime, String)> {
+        let path = state_file(name);
+        let clock = FakeClock::at(9, 0);
+        let mut first = scheduler_missing(&clock, &["every 45m | Water"], missed);
+        first.persist(&path);
+        assert_eq!(times(&run(&mut first, &clock, 60)), ["09:45"]);
+        first.acknowledge();
+        drop(first);
+
+        clock.advance(4 * 60 - 1);
+        let mut second = scheduler_missing(&clock, &["every 45m | Water"], missed);
+        second.persist(&path);
+        let fired = run(&mut second, &clock, 60);
+        let _ = fs::remove_file(&path);
+        fired
+    }
+
+    #[test]
+    fn missed_reminders_fire_once_after_a_restart() {
+        assert_eq!(times(&restart_after_missing("once", Missed::FireOnce)), ["14:00", "14:45"]);
+    }
+
+    #[test]
+    fn missed_reminders_all_fire_up_to_the_limit() {
+        // Missed at 10:30, 11:15, 12:00, 12:45 and 13:30, counted up to the limit
+        let fired = restart_after_missing("all", Missed::FireAll);
+        assert_eq!(times(&fired), ["14:00", "14:15"]);
+        assert_eq!(fired[0].1, format!("Water <c=#888>×{}</c>", utils::MISSED_REMINDER_LIMIT));
+    }
+
+    #[test]
+    fn missed_reminders_can_be_skipped() {
+        // 45 minutes from the restart
+        assert_eq!(times(&restart_after_missing("skip", Missed::Skip)), ["14:44"]);
+    }
+
+    #[test]
+    fn sleeping_counts_as_missing() {
+        let lines = ["at 10:00 | A", "at 11:00 | B"];
+        let clock = FakeClock::at(9, 0);
+        let mut skipping = scheduler_missing(&clock, &lines, Missed::Skip);
+        let mut once = scheduler_missing(&clock, &lines, Missed::FireOnce);
+        // Asleep from 09:00 to 12:00
+        clock.advance(3 * 60 - 1);
+        assert!(run(&mut skipping, &clock, 60).is_empty());
+        clock.advance(-60);
+        assert_eq!(texts(&run(&mut once, &clock, 60)), ["A", "B"]);
+    }
+}
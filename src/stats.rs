@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Instant;
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::deck::Card;
+use crate::review::Grade;
+use crate::utils;
+
+// Learning statistics, to see whether the parrot is actually teaching anything. Every
+// card shown counts as an impression, and every answer is recorded with how long it took.
+// Unlike the review state, which only keeps what the scheduler needs, this is a log
+// kept for people: it is saved as JSON after every change and can be exported as CSV,
+// as JSON and as an HTML progress report (on exit, or with `parrot-pet --report`).
+
+/// Statistics for a single card.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CardStats {
+    pub term: String,
+    pub reading: Option<String>,
+    pub meaning: String,
+    pub deck: String,
+    pub impressions: u32,    // Times the card was shown
+    pub correct: u32,
+    pub incorrect: u32,
+    pub streak: u32,         // Correct answers in a row right now
+    pub best_streak: u32,
+    pub answer_seconds: f32, // Total time taken over all answers
+    pub first_seen: Option<NaiveDate>,
+    pub last_seen: Option<NaiveDate>,
+}
+
+impl CardStats {
+    pub fn answers(&self) -> u32 {
+        self.correct + self.incorrect
+    }
+
+    pub fn accuracy(&self) -> Option<f32> {
+        (self.answers() > 0).then(|| self.correct as f32 / self.answers() as f32)
+    }
+
+    pub fn average_seconds(&self) -> Option<f32> {
+        (self.answers() > 0).then(|| self.answer_seconds / self.answers() as f32)
+    }
+}
+
+/// Totals for one day.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct DayStats {
+    pub impressions: u32,
+    pub correct: u32,
+    pub incorrect: u32,
+    pub new_cards: u32, // Cards seen for the first time
+}
+
+// One card's row in the CSV and JSON exports
+#[derive(Debug, PartialEq, Serialize)]
+struct ExportRow<'a> {
+    term: &'a str,
+    reading: Option<&'a str>,
+    meaning: &'a str,
+    deck: &'a str,
+    impressions: u32,
+    correct: u32,
+    incorrect: u32,
+    accuracy: Option<f32>,        // Rounded to 0.01
+    streak: u32,
+    best_streak: u32,
+    average_seconds: Option<f32>, // Rounded to 0.1
+    first_seen: Option<NaiveDate>,
+    last_seen: Option<NaiveDate>,
+}
+
+impl<'a> ExportRow<'a> {
+    fn new(stats: &'a CardStats) -> Self {
+        ExportRow {
+            term: &stats.term,
+            reading: stats.reading.as_deref(),
+            meaning: &stats.meaning,
+            deck: &stats.deck,
+            impressions: stats.impressions,
+            correct: stats.correct,
+            incorrect: stats.incorrect,
+            accuracy: stats.accuracy().map(|accuracy| (accuracy * 100.0).round() / 100.0),
+            streak: stats.streak,
+            best_streak: stats.best_streak,
+            average_seconds: stats.average_seconds().map(|seconds| (seconds * 10.0).round() / 10.0),
+            first_seen: stats.first_seen,
+            last_seen: stats.last_seen,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StatsFile {
+    cards: BTreeMap<String, CardStats>, // By card key, see Card::key
+    days: BTreeMap<NaiveDate, DayStats>,
+}
+
+pub struct Stats {
+    path: String,
+    data: StatsFile,
+    shown: Option<(String, Instant)>, // Card in the bubble and when it appeared, to time the answer
+}
+
+impl Stats {
+    /// Loads saved statistics, starting fresh if the file is missing or unreadable.
+    pub fn load(path: &str) -> Self {
+        let data = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Failed to read statistics from {}: {}", path, e);
+                StatsFile::default()
+            }),
+            Err(_) => StatsFile::default(),
+        };
+        Stats { path: path.to_string(), data, shown: None }
+    }
+
+    fn save(&self) {
+        match serde_json::to_string_pretty(&self.data) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    eprintln!("Failed to save statistics to {}: {}", self.path, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to save statistics: {}", e),
+        }
+    }
+
+    /// Records that `card` from the deck `deck` has just been shown.
+    pub fn shown(&mut self, card: &Card, deck: &str) {
+        let today = Local::now().date_naive();
+        let key = card.key();
+        let stats = self.data.cards.entry(key.clone()).or_default();
+        // Keep the card's text current, the deck may have been edited
+        stats.term = card.term.clone();
+        stats.reading = card.reading.clone();
+        stats.meaning = card.meaning.clone();
+        stats.deck = deck.to_string();
+        stats.impressions += 1;
+        let day = self.data.days.entry(today).or_default();
+        if stats.first_seen.is_none() {
+            stats.first_seen = Some(today);
+            day.new_cards += 1;
+        }
+        stats.last_seen = Some(today);
+        day.impressions += 1;
+        self.shown = Some((key, Instant::now()));
+        self.save();
+    }
+
+    /// Records an answer for `card`, timed from when it was shown.
+    pub fn answered(&mut self, card: &Card, grade: Grade) {
+        let key = card.key();
+        let seconds = match self.shown.take() {
+            Some((shown, at)) if shown == key => at.elapsed().as_secs_f32(),
+            _ => 0.0,
+        };
+        let stats = self.data.cards.entry(key).or_default();
+        let day = self.data.days.entry(Local::now().date_naive()).or_default();
+        stats.answer_seconds += seconds;
+        match grade {
+            Grade::Good => {
+                stats.correct += 1;
+                stats.streak += 1;
+                stats.best_streak = stats.best_streak.max(stats.streak);
+                day.correct += 1;
+            }
+            Grade::Again => {
+                stats.incorrect += 1;
+                stats.streak = 0;
+                day.incorrect += 1;
+            }
+        }
+        self.save();
+    }
+
+    // Days in a row, up to `today`, with at least one card shown
+    fn study_streak(&self, today: NaiveDate) -> u32 {
+        let mut day = today;
+        let mut streak = 0;
+        while self.data.days.get(&day).is_some_and(|stats| stats.impressions > 0) {
+            streak += 1;
+            let Some(previous) = day.pred_opt() else { break };
+            day = previous;
+        }
+        streak
+    }
+
+    /// Writes the CSV and JSON exports and the HTML progress report.
+    pub fn export(&self) {
+        if let Err(e) = self.write_csv(utils::STATS_CSV_FILE) {
+            eprintln!("Failed to export statistics to {}: {}", utils::STATS_CSV_FILE, e);
+        }
+        match serde_json::to_string_pretty(&self.rows()) {
+            Ok(json) => {
+                if let Err(e) = fs::write(utils::STATS_JSON_FILE, json) {
+                    eprintln!("Failed to export statistics to {}: {}", utils::STATS_JSON_FILE, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to export statistics: {}", e),
+        }
+        if let Err(e) = fs::write(utils::STATS_REPORT_FILE, self.report()) {
+            eprintln!("Failed to write progress report {}: {}", utils::STATS_REPORT_FILE, e);
+        }
+    }
+
+    // The per-card rows both exports write
+    fn rows(&self) -> Vec<ExportRow<'_>> {
+        self.data.cards.values().map(ExportRow::new).collect()
+    }
+
+    fn write_csv(&self, path: &str) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        for row in self.rows() {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // The HTML progress report: totals, answers per day and how each card is going,
+    // hardest cards first
+    fn report(&self) -> String {
+        let cards = &self.data.cards;
+        let correct: u32 = cards.values().map(|stats| stats.correct).sum();
+        let answers: u32 = cards.values().map(|stats| stats.answers()).sum();
+        let seconds: f32 = cards.values().map(|stats| stats.answer_seconds).sum();
+        let learned = cards.values().filter(|stats| stats.streak >= utils::STATS_LEARNED_STREAK).count();
+
+        let mut html = String::from(REPORT_HEAD);
+        html.push_str(&format!("<p>Generated {}</p>\n<ul>\n", Local::now().format("%Y-%m-%d %H:%M")));
+        html.push_str(&format!("<li>Cards seen: <b>{}</b>, learned: <b>{}</b> ({} right in a row)</li>\n", cards.len(), learned, utils::STATS_LEARNED_STREAK));
+        html.push_str(&format!("<li>Answers: <b>{}</b>, right: <b>{}</b></li>\n", answers, percent(correct, answers)));
+        if answers > 0 {
+            html.push_str(&format!("<li>Average time to answer: <b>{:.1} s</b></li>\n", seconds / answers as f32));
+        }
+        html.push_str(&format!("<li>Days in a row: <b>{}</b></li>\n</ul>\n", self.study_streak(Local::now().date_naive())));
+
+        html.push_str("<h2>By day</h2>\n<table>\n<tr><th>Date</th><th>Shown</th><th>New</th><th>Right</th><th>Wrong</th><th>Accuracy</th></tr>\n");
+        for (date, day) in self.data.days.iter().rev() {
+            let answered = day.correct + day.incorrect;
+            let width = (day.correct * 100).checked_div(answered).unwrap_or(0);
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><div class=\"bar\"><div style=\"width:{}%\"></div></div> {}</td></tr>\n",
+                date, day.impressions, day.new_cards, day.correct, day.incorrect, width, percent(day.correct, answered)
+            ));
+        }
+        html.push_str("</table>\n");
+
+        let mut sorted: Vec<&CardStats> = cards.values().collect();
+        sorted.sort_by(|a, b| {
+            let accuracy = |stats: &CardStats| stats.accuracy().unwrap_or(f32::MAX);
+            accuracy(a).total_cmp(&accuracy(b)).then(b.incorrect.cmp(&a.incorrect))
+        });
+        html.push_str("<h2>By card</h2>\n<table>\n<tr><th>Term</th><th>Reading</th><th>Meaning</th><th>Deck</th><th>Shown</th><th>Right</th><th>Wrong</th><th>Accuracy</th><th>Streak</th><th>Best</th><th>Avg time</th><th>Last seen</th></tr>\n");
+        for stats in sorted {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&stats.term),
+                escape(stats.reading.as_deref().unwrap_or("")),
+                escape(&stats.meaning),
+                escape(&stats.deck),
+                stats.impressions,
+                stats.correct,
+                stats.incorrect,
+                percent(stats.correct, stats.answers()),
+                stats.streak,
+                stats.best_streak,
+                stats.average_seconds().map(|seconds| format!("{:.1} s", seconds)).unwrap_or_default(),
+                stats.last_seen.map(|date| date.to_string()).unwrap_or_default(),
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+const REPORT_HEAD: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Parrot progress</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border-bottom: 1px solid #ddd; padding: 4px 10px; text-align: left; }
+.bar { display: inline-block; width: 100px; height: 10px; background: #eee; }
+.bar div { height: 100%; background: #4a4; }
+</style>
+</head>
+<body>
+<h1>Parrot progress</h1>
+";
+
+fn percent(part: u32, whole: u32) -> String {
+    (part * 100).checked_div(whole).map_or("-".to_string(), |percent| format!("{}%", percent))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answered(correct: u32, incorrect: u32, answer_seconds: f32) -> CardStats {
+        CardStats { term: "猫".to_string(), correct, incorrect, answer_seconds, ..Default::default() }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    // Statistics with a card shown on each of `days` (of March)
+    fn studied(days: &[u32]) -> Stats {
+        let mut stats = Stats::load("");
+        for day in days {
+            stats.data.days.insert(date(*day), DayStats { impressions: 1, ..Default::default() });
+        }
+        stats
+    }
+
+    #[test]
+    fn accuracy_and_time_are_per_answer() {
+        let stats = answered(2, 1, 4.5);
+        assert_eq!(stats.accuracy(), Some(2.0 / 3.0));
+        assert_eq!(stats.average_seconds(), Some(1.5));
+        // Nothing to work out before the first answer
+        assert_eq!(answered(0, 0, 0.0).accuracy(), None);
+        assert_eq!(answered(0, 0, 0.0).average_seconds(), None);
+    }
+
+    #[test]
+    fn percent_rounds_down_and_handles_nothing() {
+        assert_eq!(percent(2, 3), "66%");
+        assert_eq!(percent(3, 3), "100%");
+        assert_eq!(percent(0, 0), "-");
+    }
+
+    #[test]
+    fn escape_makes_text_safe_for_html() {
+        assert_eq!(escape("<b>\"Tom & Jerry\"</b>"), "&lt;b&gt;&quot;Tom &amp; Jerry&quot;&lt;/b&gt;");
+        assert_eq!(escape("猫"), "猫");
+    }
+
+    #[test]
+    fn streak_counts_days_in_a_row_up_to_today() {
+        assert_eq!(studied(&[3, 5, 6, 7]).study_streak(date(7)), 3);
+        // A day with nothing shown breaks it, as does not having studied today yet
+        assert_eq!(studied(&[6, 7]).study_streak(date(8)), 0);
+        let mut stats = studied(&[5, 7]);
+        stats.data.days.insert(date(6), DayStats::default());
+        assert_eq!(stats.study_streak(date(7)), 1);
+    }
+
+    #[test]
+    fn exports_hold_the_computed_figures() {
+        let mut stats = Stats::load("");
+        stats.data.cards.insert("猫 [ねこ]".to_string(), CardStats { reading: Some("ねこ".to_string()), last_seen: Some(date(7)), ..answered(2, 1, 4.5) });
+        stats.data.cards.insert("犬".to_string(), CardStats { term: "犬".to_string(), ..answered(0, 0, 0.0) });
+        let json: serde_json::Value = serde_json::to_value(stats.rows()).unwrap();
+        assert_eq!(json[0]["term"], "犬");
+        assert_eq!(json[0]["accuracy"], serde_json::Value::Null);
+        assert_eq!(json[1]["reading"], "ねこ");
+        assert_eq!(json[1]["accuracy"].as_f64(), Some(0.67f32 as f64));
+        assert_eq!(json[1]["average_seconds"].as_f64(), Some(1.5));
+        assert_eq!(json[1]["last_seen"], "2026-03-07");
+
+        let mut csv = csv::Writer::from_writer(Vec::new());
+        for row in stats.rows() {
+            csv.serialize(row).unwrap();
+        }
+        let csv = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "term,reading,meaning,deck,impressions,correct,incorrect,accuracy,streak,best_streak,average_seconds,first_seen,last_seen");
+        assert_eq!(lines[2], "猫,ねこ,,,0,2,1,0.67,0,0,1.5,,2026-03-07");
+    }
+}
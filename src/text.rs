@@ -0,0 +1,400 @@
+use fontdue::Font;
+use unicode_bidi::{Level, ParagraphBidiInfo};
+
+use crate::emoji::EmojiFont;
+use crate::markup::{Style, StyledChar};
+
+// Text layout for the speech bubble, driven by the font's own metrics
+// (advance widths, kerning pairs, glyph bounds and line metrics).
+// Mixed left-to-right / right-to-left text is wrapped in logical order and then each
+// line is reordered for display with the Unicode Bidirectional Algorithm (UAX #9).
+// Glyphs are not shaped, so Arabic letters are drawn in their isolated forms.
+
+/// The fonts bubble text is drawn with. Bold and emoji are optional.
+pub struct Fonts {
+    pub regular: Font,
+    pub bold: Option<Font>,
+    pub emoji: Option<EmojiFont>,
+}
+
+/// A glyph placed in bitmap coordinates, ready to be rasterized.
+pub struct PlacedGlyph {
+    pub ch: char,
+    pub style: Style,
+    pub emoji: bool, // Drawn in colour from the emoji font
+    pub x: i32, // Left edge of the glyph bitmap
+    pub y: i32, // Top edge of the glyph bitmap
+    index: usize, // Position of the character in its line, used to restore reading order
+}
+
+/// Picks the font a style should be drawn with, falling back to regular if no bold font is loaded.
+fn font_for<'a>(fonts: &'a Fonts, style: &Style) -> &'a Font {
+    match (&fonts.bold, style.bold) {
+        (Some(bold), true) => bold,
+        _ => &fonts.regular,
+    }
+}
+
+/// Returns the emoji font if this character should be drawn in colour from it:
+/// pictographs always are, anything else only if the text font has no glyph for it.
+fn emoji_for(fonts: &Fonts, ch: char) -> Option<&EmojiFont> {
+    let emoji = fonts.emoji.as_ref()?;
+    let pictograph = ch >= '\u{1F000}' || ('\u{2600}'..='\u{27BF}').contains(&ch);
+    if (pictograph || fonts.regular.lookup_glyph_index(ch) == 0) && emoji.has_glyph(ch) {
+        Some(emoji)
+    } else {
+        None
+    }
+}
+
+/// Variation selectors and zero width joiners only steer emoji presentation and take no space.
+fn is_invisible(ch: char) -> bool {
+    matches!(ch, '\u{FE0E}' | '\u{FE0F}' | '\u{200D}')
+}
+
+/// A line of styled text, as produced by `wrap`.
+#[derive(Clone)]
+pub struct Line {
+    pub chars: Vec<StyledChar>, // Display order, left to right
+    pub logical: Vec<usize>,    // Reading order position of each displayed character
+    pub rtl: bool,              // Part of a right-to-left paragraph, aligned to the right edge
+    pub region: Option<usize>,  // Clickable area this line belongs to, e.g. a quiz answer
+}
+
+impl Line {
+    /// A left-to-right line displayed as given.
+    pub fn ltr(chars: Vec<StyledChar>) -> Self {
+        let logical = (0..chars.len()).collect();
+        Line { chars, logical, rtl: false, region: None }
+    }
+
+    /// Reorders a line from reading order into display order.
+    fn visual(chars: Vec<StyledChar>, rtl: bool) -> Self {
+        let text: String = chars.iter().map(|c| c.ch).collect();
+        let paragraph_level = if rtl { Level::rtl() } else { Level::ltr() };
+        let bidi = ParagraphBidiInfo::new(&text, Some(paragraph_level));
+        if !rtl && bidi.is_pure_ltr {
+            return Line::ltr(chars);
+        }
+        // Levels and runs are reported per byte, map them back to characters
+        let char_starts: Vec<usize> = text.char_indices().map(|(byte, _)| byte).collect();
+        let (levels, runs) = bidi.visual_runs(0..text.len());
+        let mut logical = Vec::with_capacity(chars.len());
+        for run in runs {
+            let first = char_starts.partition_point(|&byte| byte < run.start);
+            let end = char_starts.partition_point(|&byte| byte < run.end);
+            if levels[run.start].is_rtl() {
+                logical.extend((first..end).rev());
+            } else {
+                logical.extend(first..end);
+            }
+        }
+        let chars = logical.iter()
+            .map(|&i| {
+                let mut styled = chars[i];
+                if levels[char_starts[i]].is_rtl() {
+                    styled.ch = mirror(styled.ch);
+                }
+                styled
+            })
+            .collect();
+        Line { chars, logical, rtl, region: None }
+    }
+}
+
+/// Paired punctuation is drawn mirrored inside right-to-left runs, so "(" still opens the parenthesis.
+fn mirror(ch: char) -> char {
+    match ch {
+        '(' => ')', ')' => '(',
+        '[' => ']', ']' => '[',
+        '{' => '}', '}' => '{',
+        '<' => '>', '>' => '<',
+        '«' => '»', '»' => '«',
+        _ => ch,
+    }
+}
+
+/// True for scripts written without spaces, where a line may break between any two characters.
+fn breaks_anywhere(ch: char) -> bool {
+    matches!(ch, '\u{3000}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF00}'..='\u{FFEF}')
+}
+
+/// Punctuation that must not start a line (a minimal kinsoku rule).
+fn no_line_start(ch: char) -> bool {
+    matches!(ch, '、' | '。' | '，' | '．' | '」' | '』' | '）' | '】' | 'ー' | '・' | '！' | '？' | ',' | '.' | ';' | ':' | '!' | '?' | ')')
+}
+
+/// Splits a run of text into units that may not be broken across lines:
+/// words, single whitespace characters and single CJK characters.
+fn break_units(text: &[StyledChar]) -> Vec<&[StyledChar]> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    for (i, styled) in text.iter().enumerate() {
+        let prev_breaks = i > 0 && (text[i - 1].ch.is_whitespace() || breaks_anywhere(text[i - 1].ch));
+        let split_before = styled.ch.is_whitespace()
+            || (!no_line_start(styled.ch) && (breaks_anywhere(styled.ch) || prev_breaks));
+        if split_before && i > start {
+            units.push(&text[start..i]);
+            start = i;
+        }
+    }
+    if start < text.len() {
+        units.push(&text[start..]);
+    }
+    units
+}
+
+/// Breaks styled text into lines no wider than `max_width` pixels, honouring explicit line breaks.
+/// Lines are returned in display order; each explicit line is its own bidi paragraph whose
+/// direction comes from its first strong character.
+pub fn wrap(fonts: &Fonts, text: &[StyledChar], max_width: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for hard_line in text.split(|c| c.ch == '\n') {
+        let paragraph: String = hard_line.iter().map(|c| c.ch).collect();
+        let rtl = ParagraphBidiInfo::new(&paragraph, None).paragraph_level.is_rtl();
+        let mut line: Vec<StyledChar> = Vec::new();
+        for unit in break_units(hard_line) {
+            let is_space = unit.iter().all(|c| c.ch.is_whitespace());
+            if is_space && line.is_empty() {
+                continue;
+            }
+            let mut candidate = line.clone();
+            candidate.extend_from_slice(unit);
+            if is_space || line_width(fonts, &candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                trim_end(&mut line);
+                lines.push(Line::visual(std::mem::take(&mut line), rtl));
+            }
+            // A single word wider than the bubble is broken between characters
+            for &styled in unit {
+                line.push(styled);
+                if line.len() > 1 && line_width(fonts, &line) > max_width {
+                    let overflow = line.pop().unwrap();
+                    lines.push(Line::visual(std::mem::replace(&mut line, vec![overflow]), rtl));
+                }
+            }
+        }
+        trim_end(&mut line);
+        lines.push(Line::visual(line, rtl));
+    }
+    lines
+}
+
+fn trim_end(line: &mut Vec<StyledChar>) {
+    while line.last().is_some_and(|c| c.ch.is_whitespace()) {
+        line.pop();
+    }
+}
+
+/// Width in pixels of a line of styled text once laid out.
+pub fn line_width(fonts: &Fonts, line: &[StyledChar]) -> f32 {
+    let mut scratch = Vec::new();
+    layout_line(fonts, line, 0.0, 0.0, &mut scratch)
+}
+
+/// Height in pixels a line takes up, including the gap to the next one.
+pub fn line_height(fonts: &Fonts, line: &[StyledChar]) -> f32 {
+    line_metrics(fonts, line).1 * crate::utils::LINE_HEIGHT
+}
+
+/// Groups lines into pages no taller than `max_height` pixels. Every page holds at least one line.
+pub fn paginate(fonts: &Fonts, lines: Vec<Line>, max_height: f32) -> Vec<Vec<Line>> {
+    let mut pages = Vec::new();
+    let mut page: Vec<Line> = Vec::new();
+    let mut page_height = 0.0;
+    for line in lines {
+        let height = line_height(fonts, &line.chars);
+        if !page.is_empty() && page_height + height > max_height {
+            pages.push(std::mem::take(&mut page));
+            page_height = 0.0;
+        }
+        // Blank lines at the top of a page would only waste space
+        if page.is_empty() && line.chars.is_empty() {
+            continue;
+        }
+        page_height += height;
+        page.push(line);
+    }
+    if !page.is_empty() || pages.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Finds the line drawn `y` pixels below the top of the text area, using the same
+/// line advance as `layout`.
+pub fn line_at<'a>(fonts: &Fonts, lines: &'a [Line], y: f32) -> Option<&'a Line> {
+    if y < 0.0 {
+        return None;
+    }
+    let mut line_top = 0.0;
+    for line in lines {
+        line_top += line_height(fonts, &line.chars);
+        if y < line_top {
+            return Some(line);
+        }
+    }
+    None
+}
+
+/// Lays out lines of styled characters in a text area `width` pixels wide.
+/// `x` is the left margin and `y` the top of the first line; every line sits on a
+/// shared baseline and is advanced by the font's line metrics. Right-to-left lines
+/// are aligned to the right edge. Glyphs come back in reading order.
+pub fn layout(fonts: &Fonts, lines: &[Line], x: i32, y: i32, width: i32) -> Vec<PlacedGlyph> {
+    let mut glyphs = Vec::new();
+    let mut line_top = y as f32;
+    for line in lines {
+        // The tallest run on the line decides where the baseline goes and how far the next line drops
+        let (ascent, new_line_size) = line_metrics(fonts, &line.chars);
+        let start_x = if line.rtl {
+            (x + width) as f32 - line_width(fonts, &line.chars)
+        } else {
+            x as f32
+        };
+        let first = glyphs.len();
+        layout_line(fonts, &line.chars, start_x, line_top + ascent, &mut glyphs);
+        // Display order to reading order, so the typewriter reveals right-to-left text from the right
+        glyphs[first..].sort_by_key(|glyph| line.logical[glyph.index]);
+        line_top += new_line_size * crate::utils::LINE_HEIGHT;
+    }
+    glyphs
+}
+
+/// Places one line of glyphs on `baseline` starting at `x`, returning where the pen ends up.
+fn layout_line(fonts: &Fonts, line: &[StyledChar], x: f32, baseline: f32, glyphs: &mut Vec<PlacedGlyph>) -> f32 {
+    let mut pen_x = x;
+    let mut previous: Option<(u16, Style)> = None;
+    for (position, styled) in line.iter().enumerate() {
+        let px = styled.style.px;
+        if is_invisible(styled.ch) {
+            continue;
+        }
+        if let Some(emoji) = emoji_for(fonts, styled.ch) {
+            let metrics = emoji.metrics(styled.ch, px).unwrap_or_default();
+            glyphs.push(PlacedGlyph {
+                ch: styled.ch,
+                style: styled.style,
+                emoji: true,
+                x: (pen_x + metrics.xmin as f32).round() as i32,
+                y: (baseline - (metrics.ymin + metrics.height as i32) as f32).round() as i32,
+                index: position,
+            });
+            pen_x += metrics.advance_width;
+            previous = None;
+            continue;
+        }
+        let font = font_for(fonts, &styled.style);
+        let index = font.lookup_glyph_index(styled.ch);
+        if let Some((prev_index, prev_style)) = previous {
+            // Only kern pairs from the same font and size, mixed runs have no meaningful pair value
+            if prev_style.px == px && prev_style.bold == styled.style.bold {
+                pen_x += font.horizontal_kern_indexed(prev_index, index, px).unwrap_or(0.0);
+            }
+        }
+        let metrics = font.metrics_indexed(index, px);
+        glyphs.push(PlacedGlyph {
+            ch: styled.ch,
+            style: styled.style,
+            emoji: false,
+            x: (pen_x + metrics.xmin as f32).round() as i32,
+            // ymin is the distance from the baseline to the bottom of the glyph (positive up)
+            y: (baseline - (metrics.ymin + metrics.height as i32) as f32).round() as i32,
+            index: position,
+        });
+        pen_x += metrics.advance_width;
+        previous = Some((index, styled.style));
+    }
+    pen_x
+}
+
+/// Returns the ascent and line advance of the tallest run on a line.
+fn line_metrics(fonts: &Fonts, line: &[StyledChar]) -> (f32, f32) {
+    let mut ascent: f32 = 0.0;
+    let mut new_line_size: f32 = 0.0;
+    for styled in line {
+        let px = styled.style.px;
+        match font_for(fonts, &styled.style).horizontal_line_metrics(px) {
+            Some(metrics) => {
+                ascent = ascent.max(metrics.ascent);
+                new_line_size = new_line_size.max(metrics.new_line_size);
+            }
+            None => {
+                ascent = ascent.max(px);
+                new_line_size = new_line_size.max(px);
+            }
+        }
+    }
+    if line.is_empty() {
+        // Keep blank lines (e.g. from a double <br>) as tall as regular text
+        let px = Style::default().px;
+        return match fonts.regular.horizontal_line_metrics(px) {
+            Some(metrics) => (metrics.ascent, metrics.new_line_size),
+            None => (px, px),
+        };
+    }
+    (ascent, new_line_size)
+}
+
+/// Rasterizes placed glyphs onto a BGRA bitmap of the given size, blending the
+/// glyph coverage in its style colour over whatever is already there (e.g. the bubble).
+pub fn draw(dest: &mut [u8], width: usize, height: usize, fonts: &Fonts, glyphs: &[PlacedGlyph]) {
+    for glyph in glyphs {
+        if glyph.emoji {
+            if let Some(emoji) = &fonts.emoji {
+                draw_emoji(dest, width, height, emoji, glyph);
+            }
+            continue;
+        }
+        let (metrics, bitmap) = font_for(fonts, &glyph.style).rasterize(glyph.ch, glyph.style.px);
+        let [r, g, b] = glyph.style.color;
+        let ink = [b as u32, g as u32, r as u32]; // BGRA order
+        for y in 0..metrics.height {
+            for x in 0..metrics.width {
+                let coverage = bitmap[y * metrics.width + x] as u32;
+                if coverage == 0 {
+                    continue;
+                }
+                let dest_x = glyph.x + x as i32;
+                let dest_y = glyph.y + y as i32;
+                if dest_x < 0 || dest_x >= width as i32 || dest_y < 0 || dest_y >= height as i32 {
+                    continue;
+                }
+                let idx = (dest_y as usize * width + dest_x as usize) * 4;
+                // Premultiplied "over": ink weighted by coverage on top of the faded background
+                for channel in 0..3 {
+                    dest[idx + channel] = ((ink[channel] * coverage + dest[idx + channel] as u32 * (255 - coverage)) / 255) as u8;
+                }
+                dest[idx + 3] = (coverage + dest[idx + 3] as u32 * (255 - coverage) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Composites a colour glyph (premultiplied BGRA) over the bitmap.
+fn draw_emoji(dest: &mut [u8], width: usize, height: usize, emoji: &EmojiFont, glyph: &PlacedGlyph) {
+    let Some((metrics, pixels)) = emoji.rasterize(glyph.ch, glyph.style.px, glyph.style.color) else {
+        return;
+    };
+    for y in 0..metrics.height {
+        for x in 0..metrics.width {
+            let src_idx = (y * metrics.width + x) * 4;
+            let alpha = pixels[src_idx + 3] as u32;
+            if alpha == 0 {
+                continue;
+            }
+            let dest_x = glyph.x + x as i32;
+            let dest_y = glyph.y + y as i32;
+            if dest_x < 0 || dest_x >= width as i32 || dest_y < 0 || dest_y >= height as i32 {
+                continue;
+            }
+            let idx = (dest_y as usize * width + dest_x as usize) * 4;
+            for channel in 0..4 {
+                dest[idx + channel] = (pixels[src_idx + channel] as u32 + dest[idx + channel] as u32 * (255 - alpha) / 255) as u8;
+            }
+        }
+    }
+}
@@ -16,6 +16,11 @@ impl SimpleRng {
         }
     }
 
+    /// Creates a generator that always gives the same numbers for the same `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
     /// Generates the next pseudo-random u64 number using a linear congruential generator.
     /// The `pub` keyword makes this method visible to other modules.
     pub fn next(&mut self) -> u64 {
@@ -32,6 +37,14 @@ impl SimpleRng {
         // and normalize it to the range [0.0, 1.0).
         (self.next() & 0xFFFFFF) as f32 / 0xFFFFFF as f32
     }
+
+    /// Puts the items in a random order (Fisher-Yates shuffle).
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_f32() * (i + 1) as f32) as usize;
+            items.swap(i, j.min(i));
+        }
+    }
 }
 
 // Configuration variables for the parrot pet
@@ -41,12 +54,64 @@ pub const COLOR: windows::Win32::Foundation::COLORREF = windows::Win32::Foundati
 // Bubble and text configuration
 pub const PARROT_SCALE: u32 = 4; // Parrot image scale divisor
 pub const BUBBLE_SCALE: u32 = 4; // Bubble image scale divisor
-pub const BUBBLE_OFFSET_X: i32 = 40; // Bubble X offset relative to parrot
-pub const BUBBLE_OFFSET_Y: i32 = -50; // Bubble Y offset relative to parrot
+pub const BUBBLE_OFFSET_X: i32 = 40; // How far the bubble overlaps the parrot (towards its tail)
+pub const BUBBLE_OFFSET_Y: i32 = -250; // Bubble top relative to the parrot's top when beside it
 pub const BUBBLE_TEXT_START_X: i32 = 80; // Text start X inside bubble
 pub const BUBBLE_TEXT_START_Y: i32 = 120; // Text start Y inside bubble
-pub const FIRST_LINE_SPACING: i32 = 32; // Space after first line in bubble
-pub const OTHER_LINE_SPACING: i32 = 20; // Space after other lines in bubble
-pub const FONT_SIZE_HEAD: f32 = 25.0; // Font size for first two chars
-pub const FONT_SIZE_MAIN: f32 = 18.0; // Font size for rest of text
-pub const REMINDER_INTERVAL: u64 = 1800; // How often to queue a reminder (in seconds) 30min/1800
\ No newline at end of file
+pub const BUBBLE_TEXT_WIDTH: i32 = 150; // Width of the text area inside the bubble, longer lines wrap
+pub const BUBBLE_TEXT_HEIGHT: i32 = 90; // Height of the text area inside the bubble, overflow goes to the next page
+pub const LINE_HEIGHT: f32 = 1.0; // Line height multiplier on top of the font's line metrics
+pub const FONT_SIZE_HEAD: f32 = 25.0; // Font size for <s> markup without a value (headwords)
+pub const FONT_SIZE_MAIN: f32 = 18.0; // Default font size for message text
+pub const BUBBLE_MIN_PAGE_FRAMES: u32 = 240; // Shortest time a bubble page stays up (4 seconds at 60fps)
+pub const BUBBLE_READING_FRAMES_PER_CHAR: u32 = 6; // Extra reading time per character on a page
+pub const PAGE_INDICATOR_SIZE: f32 = 12.0; // Font size of the "1/3" page indicator
+pub const BUBBLE_POP_FRAMES: u32 = 12; // Frames for the bubble to scale/fade in when opening
+pub const BUBBLE_FADE_FRAMES: u32 = 20; // Frames for the bubble to fade out before closing
+pub const TYPEWRITER_ENABLED: bool = true; // Reveal bubble text character by character
+pub const TYPEWRITER_CHARS_PER_SECOND: f32 = 30.0; // Typewriter speed in Latin characters per second
+pub const TYPEWRITER_CJK_WEIGHT: f32 = 2.0; // A CJK character takes as long as this many Latin ones
+pub const MESSAGE_BUBBLE_STYLE: crate::bubble::BubbleStyle = crate::bubble::BubbleStyle::Speech; // Bubble for messages.txt unless the message picks one
+pub const REMINDER_BUBBLE_STYLE: crate::bubble::BubbleStyle = crate::bubble::BubbleStyle::Shout; // Bubble for reminders, so they stand out from trivia
+pub const REMINDER_BADGE_SIZE: u32 = 18; // Diameter of the counter of waiting reminders on the parrot
+pub const REMINDER_BADGE_COLOR: [u8; 3] = [220, 60, 50]; // Colour of that counter (RGB)
+pub const DECK_DIR: &str = "decks"; // Every deck file in this folder is loaded
+pub const DECK_FILES: &[&str] = &["deck.toml", "deck.json", "deck.csv", "deck.tsv", "deck.apkg", "messages.txt"]; // Without DECK_DIR, the first of these found is the only deck
+pub const ACTIVE_DECKS_FILE: &str = "active_decks.toml"; // Which decks and tags are shown when (see library.rs)
+pub const DEFAULT_PICKER: crate::picker::Strategy = crate::picker::Strategy::Review; // How cards are picked unless the deck file says otherwise
+pub const READING_SCRIPT: crate::kana::Script = crate::kana::Script::AsWritten; // How the bracketed reading is written: AsWritten, Hiragana, Katakana or Romaji
+pub const READING_LINE: Option<crate::kana::Script> = None; // e.g. Some(Romaji) adds the reading in that script as a second line under the term
+pub const DICTIONARY_FILES: &[&str] = &["JMdict_e", "JMdict_e.xml", "kanjidic2.xml"]; // Local JMdict/KANJIDIC2 files used to fill in bare words
+pub const DICTIONARY_GLOSSES: usize = 3; // Most meanings (and kanji readings) taken from a dictionary entry
+pub const ANKI_FIELDS: &[&str] = &["term", "reading", "meaning", "example"]; // Card field for each Anki note field in order, "" skips one
+pub const REVIEW_FILE: &str = "reviews.json"; // Where spaced repetition progress is saved
+pub const WORD_OF_THE_DAY_FILE: &str = "word_of_the_day.txt"; // Date the word of the day was last announced, so a restart doesn't repeat it
+pub const STATS_FILE: &str = "stats.json"; // Learning statistics, saved after every card shown or answered
+pub const STATS_CSV_FILE: &str = "stats.csv"; // Per-card statistics export, written on exit and by --report
+pub const STATS_JSON_FILE: &str = "stats-export.json"; // The same export as JSON, next to the CSV
+pub const STATS_REPORT_FILE: &str = "progress.html"; // HTML progress report, written on exit and by --report
+pub const STATS_LEARNED_STREAK: u32 = 3; // Right answers in a row for a card to count as learned in the report
+pub const REVIEW_AGAIN_SECONDS: u64 = 600; // A card marked "again" comes back after this long
+pub const QUIZ_MODE: bool = true; // Ask with just the term first, the rest of the card shows once answered
+pub const QUIZ_CHOICES: usize = 3; // Meanings offered to pick from in quiz mode, 0 or 1 for a plain prompt
+pub const QUIZ_ANSWER_FRAMES: u32 = 900; // Shortest time a quiz question or a reminder with its snooze/done actions stays up (15 seconds at 60fps)
+pub const CLICK_DISTANCE: i32 = 4; // A left press and release that moves less than this (in pixels) is a click, not a drag
+pub const REMINDER_FILE: &str = "reminders.txt"; // One reminder per line, optionally with a schedule (see schedule.rs)
+pub const REMINDER_INTERVAL: u64 = 1800; // How often to queue a reminder (in seconds) 30min/1800
+pub const SCHEDULER_STATE_FILE: &str = "scheduler.json"; // Round robin position and when each reminder is next due, kept across restarts
+pub const MISSED_REMINDERS: crate::scheduler::Missed = crate::scheduler::Missed::FireOnce; // Reminders missed while off or asleep: FireOnce, FireAll or Skip
+pub const MISSED_REMINDER_LIMIT: usize = 3; // Most missed occurrences of one reminder counted with FireAll
+pub const SCHEDULER_SLEEP_SECONDS: u64 = 120; // Ticks further apart than this mean the machine was asleep
+pub const REMINDER_EXPIRY_MINUTES: u32 = 120; // How long a reminder waits to be seen before it is dropped, 0 to keep it until seen
+pub const REMINDER_SNOOZE_MINUTES: u32 = 10; // How long "Snooze" puts a reminder away for
+pub const QUIET_HOURS: &str = "22:00-07:00"; // Night mode: only urgent reminders are shown and the parrot sleeps, "" to turn it off
+pub const QUIET_DAYS: &[&str] = &[]; // Nights the quiet hours start on ("weekdays", "fri,sat", see hours.rs), empty for every night
+pub const QUIET_REMINDERS: crate::scheduler::Quiet = crate::scheduler::Quiet::Defer; // Other reminders during quiet hours: Defer until morning or Suppress
+pub const NIGHT_MOVE_CHANCE: f32 = 0.2; // Chance a sleeping parrot gets up when it would move by day
+pub const NIGHT_SPEED: f32 = 0.4; // How fast it moves at night, relative to the day
+pub const NIGHT_SNORE: &str = "z Z"; // Drawn above the sleeping parrot
+pub const ACTIVITY_PHASES: &[crate::activity::Phase] = &[ // The parrot's activity cycle, repeated (see activity.rs). One phase with idle (180, 780), move_chance 1.0 and speed 1.0 moves as it always did
+    crate::activity::Phase { seconds: 300, idle: (120, 480), move_chance: 1.0, speed: 1.2 }, // Busy: 5 minutes of moving a lot
+    crate::activity::Phase { seconds: 120, idle: (300, 1200), move_chance: 0.4, speed: 0.6 }, // Calm: 2 minutes of not so much
+];
+pub const ACTIVITY_BLEND_SECONDS: u32 = 20; // How long the parrot takes to ease from one phase into the next
\ No newline at end of file